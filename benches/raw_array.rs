@@ -0,0 +1,108 @@
+use bincode::enc::write::Writer;
+use bincode::enc::EncoderImpl;
+use bincode::error::EncodeError;
+use bincode::Encode;
+use crate_spec::utils::context::PackageContext;
+use crate_spec::utils::package::gen_bincode::{
+    create_bincode_slice_decoder, encode2vec_by_bincode, BINCODE_CONFIG,
+};
+use crate_spec::utils::package::RawArrayType;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+///size of a large crate binary, e.g. a vendored -sys crate
+const CRATE_SIZE: usize = 8 << 20;
+
+fn crate_bin() -> Vec<u8> {
+    (0..CRATE_SIZE).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+struct VecWriter(Vec<u8>);
+
+impl Writer for VecWriter {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), EncodeError> {
+        self.0.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+///encode byte by byte, which is what RawArrayType<u8> did before the fast path
+fn encode_per_byte(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = EncoderImpl::new(VecWriter(Vec::with_capacity(bytes.len())), BINCODE_CONFIG);
+    for byte in bytes {
+        byte.encode(&mut encoder).unwrap();
+    }
+    encoder.into_writer().0
+}
+
+///decode byte by byte, which is what RawArrayType<u8> did before the fast path
+fn decode_per_byte(bin: &[u8]) -> Vec<u8> {
+    let mut decoder = create_bincode_slice_decoder(bin);
+    let mut out = Vec::with_capacity(bin.len());
+    for _ in 0..bin.len() {
+        out.push(bincode::Decode::decode(&mut decoder).unwrap());
+    }
+    out
+}
+
+fn bench_raw_array(c: &mut Criterion) {
+    let bytes = crate_bin();
+    let raw_array = RawArrayType::from_vec(bytes.clone());
+    let encoded = encode2vec_by_bincode(&raw_array);
+
+    let mut group = c.benchmark_group("raw_array_u8");
+    group.throughput(Throughput::Bytes(CRATE_SIZE as u64));
+    group.sample_size(10);
+    group.bench_function("encode_per_byte", |b| {
+        b.iter(|| encode_per_byte(black_box(&bytes)))
+    });
+    group.bench_function("encode", |b| {
+        b.iter(|| encode2vec_by_bincode(black_box(&raw_array)))
+    });
+    group.bench_function("decode_per_byte", |b| {
+        b.iter(|| decode_per_byte(black_box(&encoded)))
+    });
+    group.bench_function("decode", |b| {
+        b.iter(|| {
+            let mut decoder = create_bincode_slice_decoder(black_box(&encoded));
+            RawArrayType::<u8>::decode(&mut decoder, CRATE_SIZE).unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn bench_crate_package(c: &mut Criterion) {
+    let package_context = || {
+        let mut package_context = PackageContext::new();
+        package_context.set_package_info(
+            "rust-crate".to_string(),
+            "1.0.0".to_string(),
+            "MIT".to_string(),
+            vec![],
+        );
+        package_context.add_crate_bin(crate_bin());
+        package_context
+    };
+    let (_, _, bin) = package_context().encode_to_crate_package().unwrap();
+
+    let mut group = c.benchmark_group("crate_package");
+    group.throughput(Throughput::Bytes(bin.len() as u64));
+    group.sample_size(10);
+    group.bench_function("encode", |b| {
+        b.iter_batched(
+            package_context,
+            |mut package_context| package_context.encode_to_crate_package().unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("decode", |b| {
+        b.iter(|| {
+            PackageContext::new()
+                .decode_from_crate_package(black_box(&bin))
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_raw_array, bench_crate_package);
+criterion_main!(benches);
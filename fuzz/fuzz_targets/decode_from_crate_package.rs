@@ -0,0 +1,13 @@
+#![no_main]
+
+use crate_spec::utils::context::PackageContext;
+use crate_spec::utils::pkcs::PKCS;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    //append fingerprint so that input reaches the decoder behind the fingerprint check
+    let mut bin = data.to_vec();
+    bin.extend(PKCS::new().gen_digest_256(data).unwrap());
+    let _ = PackageContext::new().decode_from_crate_package(&bin);
+    let _ = PackageContext::new().decode_from_crate_package_ref(&bin);
+});
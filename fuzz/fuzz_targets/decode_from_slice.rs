@@ -0,0 +1,9 @@
+#![no_main]
+
+use crate_spec::utils::package::{CratePackage, CratePackageRef};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = CratePackage::decode_from_slice(data);
+    let _ = CratePackageRef::decode_from_slice(data);
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use crate_spec::utils::context::StringTable;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = StringTable::new().read_bytes(data);
+});
@@ -1,9 +1,13 @@
 use crate::pack::{pack_context, pack_name};
 use crate::unpack::unpack_context;
 use clap::Parser;
-use crate_spec::utils::context::SIGTYPE;
-use crate_spec::utils::pkcs::PKCS;
-use std::fs;
+use crate_spec::utils::codec::CODECTYPE;
+use crate_spec::utils::context::{DATASECTIONTYPE, SIGTYPE};
+use crate_spec::utils::error::CrateSpecError;
+use crate_spec::utils::pkcs::{PKCS, SIGSCHEME};
+use crate_spec::utils::verify::VerificationPolicy;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
 use std::path::PathBuf;
 use std::str::FromStr;
 
@@ -28,9 +32,33 @@ struct Args {
     ///private key path
     #[clap(short, long, required = false)]
     pkey_path: Option<String>,
+    ///intermediate certificate file paths, from signer certificate up to root-ca
+    #[clap(long, required = false)]
+    chain_paths: Vec<String>,
+    ///signature scheme: cms, pkcs7, ed25519 or p256, defaults to cms
+    #[clap(long, required = false)]
+    sig_scheme: Option<String>,
+    ///public key file paths raw signatures are verified against
+    #[clap(long, required = false)]
+    keyring_paths: Vec<String>,
     ///output file path
     #[clap(short, long)]
     output: String,
+    ///scrate format version to write, defaults to the latest
+    #[clap(long, required = false)]
+    crate_version: Option<u8>,
+    ///codec for crate binary section: zstd or xz
+    #[clap(long, required = false)]
+    codec: Option<String>,
+    ///pad section index entries and sections to 4 or 8 bytes
+    #[clap(long, required = false)]
+    align: Option<u8>,
+    ///refuse to encode if a dependency can not be represented
+    #[clap(long, required = false)]
+    strict: bool,
+    ///accept the package if at least this many signatures are valid, instead of all of them
+    #[clap(long, required = false)]
+    min_valid_sigs: Option<usize>,
     #[clap()]
     input: String,
 }
@@ -39,7 +67,18 @@ fn main() {
     let args = Args::parse();
     if args.encode && !args.decode {
         //check args
-        if args.cert_path.is_none() {
+        let sig_scheme = match args.sig_scheme.as_deref() {
+            None | Some("cms") => SIGSCHEME::CMS,
+            Some("pkcs7") => SIGSCHEME::PKCS7,
+            Some("ed25519") => SIGSCHEME::ED25519,
+            Some("p256") => SIGSCHEME::P256,
+            Some(sig_scheme) => {
+                eprintln!("sig scheme {} not supported!", sig_scheme);
+                return;
+            }
+        };
+        let is_cert = matches!(sig_scheme, SIGSCHEME::PKCS7 | SIGSCHEME::CMS);
+        if is_cert && args.cert_path.is_none() {
             eprintln!("certificate not provided!");
             return;
         }
@@ -47,7 +86,7 @@ fn main() {
             eprintln!("pkey not provided!");
             return;
         }
-        if args.root_ca_paths.is_empty() {
+        if is_cert && args.root_ca_paths.is_empty() {
             eprintln!("root-ca not provided!");
             return;
         }
@@ -60,28 +99,96 @@ fn main() {
         }
 
         //pack package
-        let mut pack_context = pack_context(&args.input);
+        let mut pack_context = match pack_context(&args.input) {
+            Ok(pack_context) => pack_context,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        };
 
-        //sign package
-        let mut pkcs = PKCS::new();
-        pkcs.load_from_file_writer(
-            args.cert_path.unwrap(),
-            args.pkey_path.unwrap(),
-            args.root_ca_paths,
-        );
-        pack_context.add_sig(pkcs, SIGTYPE::CRATEBIN);
+        //report dependencies the dependency table can not represent
+        for name in pack_context.unrepresented_deps.iter() {
+            eprintln!("warning: dependency {} can not be represented!", name);
+        }
+        if args.strict && !pack_context.unrepresented_deps.is_empty() {
+            eprintln!("unrepresented dependencies found in strict mode!");
+            return;
+        }
 
-        //encode package to binary
-        let (_, _, bin) = pack_context.encode_to_crate_package();
+        //choose format version
+        if let Some(c_version) = args.crate_version {
+            if let Err(e) = pack_context.set_crate_version(c_version) {
+                eprintln!("{}", e);
+                return;
+            }
+        }
 
-        //dump binary path/<name>.scrate
+        //choose section alignment
+        if let Some(align) = args.align {
+            if let Err(e) = pack_context.set_section_align(align) {
+                eprintln!("{}", e);
+                return;
+            }
+        }
+
+        //sign package, merkle root is signed as well since version 2
+        let mut sig_types = vec![SIGTYPE::CRATEBIN];
+        if pack_context.crate_version >= 2 {
+            sig_types.push(SIGTYPE::MERKLE);
+        }
+        for sig_type in sig_types {
+            let mut pkcs = PKCS::new();
+            let loaded = match sig_scheme {
+                SIGSCHEME::PKCS7 | SIGSCHEME::CMS => pkcs
+                    .load_from_file_writer(
+                        args.cert_path.clone().unwrap(),
+                        args.pkey_path.clone().unwrap(),
+                        args.root_ca_paths.clone(),
+                    )
+                    .and_then(|_| pkcs.load_chain_from_file(args.chain_paths.clone()))
+                    .and_then(|_| pkcs.set_cert_scheme(sig_scheme)),
+                SIGSCHEME::ED25519 => {
+                    pkcs.load_raw_key_from_file(args.pkey_path.clone().unwrap(), SIGSCHEME::ED25519)
+                }
+                SIGSCHEME::P256 => {
+                    pkcs.load_raw_key_from_file(args.pkey_path.clone().unwrap(), SIGSCHEME::P256)
+                }
+            };
+            if let Err(e) = loaded {
+                eprintln!("{}", e);
+                return;
+            }
+            pack_context.add_sig(pkcs, sig_type);
+        }
+
+        //choose crate binary codec
+        if let Some(codec) = args.codec {
+            let codec = match codec.as_str() {
+                "zstd" => CODECTYPE::ZSTD,
+                "xz" => CODECTYPE::XZ,
+                _ => {
+                    eprintln!("codec {} not supported!", codec);
+                    return;
+                }
+            };
+            pack_context.set_section_codec(DATASECTIONTYPE::CRATEBIN, codec);
+        }
+
+        //encode package to path/<name>.scrate
         let mut bin_path = PathBuf::from_str(args.output.as_str()).unwrap();
         bin_path.push(pack_name(&pack_context));
-        fs::write(bin_path, bin).unwrap();
+        let mut writer = BufWriter::new(File::create(bin_path).unwrap());
+        if let Err(e) = pack_context
+            .encode_to_writer(&mut writer)
+            .and_then(|_| writer.flush().map_err(CrateSpecError::from))
+        {
+            eprintln!("{}", e);
+        }
     } else if !args.encode && args.decode {
         //check args
-        if args.root_ca_paths.is_empty() {
-            eprintln!("root-ca not provided!");
+        if args.root_ca_paths.is_empty() && args.keyring_paths.is_empty() {
+            eprintln!("root-ca or keyring not provided!");
             return;
         }
 
@@ -93,12 +200,32 @@ fn main() {
         }
 
         //decode package from binary
-        let pack_context = unpack_context(args.input.as_str(), args.root_ca_paths);
-        if pack_context.is_err() {
-            eprintln!("{}", pack_context.unwrap_err());
-            return;
+        let policy = match args.min_valid_sigs {
+            Some(min_valid) => VerificationPolicy::at_least(min_valid),
+            None => VerificationPolicy::new(),
+        };
+        let pack_context = match unpack_context(
+            args.input.as_str(),
+            args.root_ca_paths,
+            args.keyring_paths,
+            policy,
+        ) {
+            Ok(pack_context) => pack_context,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        };
+        //report signatures the policy let through
+        for sig in pack_context.verification_report.sigs.iter() {
+            if !sig.is_valid() {
+                eprintln!(
+                    "warning: sig {} {:?} - {}",
+                    sig.index, sig.status, sig.reason
+                );
+            }
         }
-        let pack_context = pack_context.unwrap();
+
         //extract crate bin file
         let mut bin_path = PathBuf::from_str(args.output.as_str()).unwrap();
         bin_path.push(format!(
@@ -116,13 +243,23 @@ fn main() {
         fs::write(
             metadata_path,
             format!(
-                "{:#?}\n{:#?}",
-                pack_context.pack_info, pack_context.dep_infos
+                "{:#?}\n{:#?}\nunrepresented_deps: {:#?}\n{:#?}\n{:#?}\n{:#?}\nsigners: {:#?}\nverification: {:#?}",
+                pack_context.pack_info,
+                pack_context.dep_infos,
+                pack_context.unrepresented_deps,
+                pack_context.feature_infos,
+                pack_context.lock_infos,
+                pack_context.provenance,
+                pack_context
+                    .sigs
+                    .iter()
+                    .map(|sig| &sig.signer)
+                    .collect::<Vec<_>>(),
+                pack_context.verification_report.sigs
             ),
         )
-            .unwrap();
+        .unwrap();
     } else {
         eprintln!("-e or -d not found!");
-        return;
     }
 }
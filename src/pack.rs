@@ -1,11 +1,21 @@
-use crate_spec::utils::context::PackageContext;
-use crate_spec::utils::from_toml::CrateToml;
+use crate_spec::utils::context::{PackageContext, ProvenanceInfo};
+use crate_spec::utils::error::CrateSpecError;
+use crate_spec::utils::from_toml::{CrateLock, CrateToml};
+use std::env;
+use std::ffi::OsString;
 use std::fs;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-fn run_cmd(cmd: &str, args: Vec<&str>, cur_dir: Option<&PathBuf>) -> Result<String, String> {
+///stdout of cmd, stderr is the error if it fails and the io error if it can not be spawned
+fn run_cmd(
+    cmd: &str,
+    args: Vec<&str>,
+    cur_dir: Option<&PathBuf>,
+) -> Result<String, CrateSpecError> {
     let mut output = Command::new(cmd);
     if !args.is_empty() {
         output.args(args);
@@ -15,73 +25,166 @@ fn run_cmd(cmd: &str, args: Vec<&str>, cur_dir: Option<&PathBuf>) -> Result<Stri
     }
     let output = output
         .output()
-        .unwrap_or_else(|_| panic!("error run cmd {}", cmd));
-    return if output.status.success() {
+        .map_err(|e| io::Error::new(e.kind(), format!("error run cmd {} - {}", cmd, e)))?;
+    if output.status.success() {
         let stdout = String::from_utf8_lossy(&output.stdout);
         Ok(stdout.to_string())
     } else {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        Err(stderr.to_string())
-    };
+        Err(CrateSpecError::Io(io::Error::other(stderr.to_string())))
+    }
 }
 
 struct Packing {
     pack_context: PackageContext,
     crate_path: PathBuf,
+    ///where cargo package writes to, known after read_crate
+    target_dir: PathBuf,
 }
 
 impl Packing {
     fn new(crate_path: &str) -> Self {
+        let crate_path = PathBuf::from_str(crate_path).unwrap();
         Packing {
             pack_context: PackageContext::new(),
-            crate_path: PathBuf::from_str(crate_path).unwrap(),
+            target_dir: crate_path.join("target"),
+            crate_path,
         }
     }
 
-    fn cmd_cargo_package(&self) {
-        let res = run_cmd(
+    fn cmd_cargo_package(&self) -> Result<(), CrateSpecError> {
+        let stdout = run_cmd(
             "cargo",
             ["package", "--allow-dirty"].to_vec(),
             Some(&self.crate_path),
-        );
-        if res.is_err() {
-            panic!("{}", res.unwrap_err());
-        } else {
-            println!("{}", res.unwrap());
-        }
+        )?;
+        println!("{}", stdout);
+        Ok(())
     }
 
-    fn read_crate(&mut self) {
+    fn read_crate(&mut self) -> Result<(), CrateSpecError> {
         //parse crate toml file
         let mut toml_path = self.crate_path.clone();
         toml_path.push("Cargo.toml");
-        let toml_path = fs::canonicalize(toml_path).unwrap();
-        let toml = CrateToml::from_file(toml_path.to_str().unwrap().to_string());
-        toml.write_info_to_package_context(&mut self.pack_context);
+        let toml_path = fs::canonicalize(toml_path)?;
+        let toml = CrateToml::from_file(toml_path.to_str().unwrap().to_string())?;
+        toml.write_info_to_package_context(&mut self.pack_context)?;
+
+        //parse Cargo.lock of the workspace root, keeping the packages this crate resolves
+        let workspace_dir = toml.workspace_dir()?;
+        self.target_dir = target_dir(
+            env::var_os("CARGO_TARGET_DIR"),
+            &self.crate_path,
+            &workspace_dir,
+        );
+        let lock_path = workspace_dir.join("Cargo.lock");
+        if lock_path.is_file() {
+            let lock = CrateLock::from_file(lock_path.to_str().unwrap().to_string())?;
+            lock.write_resolved_to_package_context(&mut self.pack_context)?;
+        }
 
         //read crate binary
         let crate_bin_file = format!(
             "{}-{}.crate",
             self.pack_context.pack_info.name, self.pack_context.pack_info.version
         );
-        let mut crate_bin_path = self.crate_path.clone();
-        crate_bin_path.push(format!("target/package/{}", crate_bin_file));
-        let crate_bin_path = fs::canonicalize(crate_bin_path).unwrap();
-        assert!(crate_bin_path.exists());
-        let bin = fs::read(crate_bin_path).unwrap();
+        let mut crate_bin_path = self.target_dir.clone();
+        crate_bin_path.push(format!("package/{}", crate_bin_file));
+        let crate_bin_path = fs::canonicalize(crate_bin_path)?;
+        let bin = fs::read(crate_bin_path)?;
 
         //write to pack_context
         self.pack_context.add_crate_bin(bin);
+        Ok(())
+    }
+
+    ///toolchain, time and commit the crate is packed with
+    fn read_provenance(&mut self) -> Result<(), CrateSpecError> {
+        let rustc = run_cmd("rustc", ["-vV"].to_vec(), Some(&self.crate_path))?;
+        let cargo = run_cmd("cargo", ["--version"].to_vec(), Some(&self.crate_path))?;
+        let mut provenance = ProvenanceInfo {
+            rustc_version: rustc.lines().next().unwrap_or_default().to_string(),
+            cargo_version: cargo.trim().to_string(),
+            host: rustc
+                .lines()
+                .find_map(|line| line.strip_prefix("host: "))
+                .unwrap_or_default()
+                .to_string(),
+            timestamp: pack_timestamp()?,
+            crate_spec_version: env!("CARGO_PKG_VERSION").to_string(),
+            ..Default::default()
+        };
+
+        //cargo package writes .cargo_vcs_info.json if the crate is in a git repository
+        let mut vcs_info_path = self.target_dir.clone();
+        vcs_info_path.push(format!(
+            "package/{}-{}/.cargo_vcs_info.json",
+            self.pack_context.pack_info.name, self.pack_context.pack_info.version
+        ));
+        if vcs_info_path.is_file() {
+            read_vcs_info(fs::read(vcs_info_path)?.as_slice(), &mut provenance)?;
+        } else {
+            eprintln!(
+                "warning: {} not found, provenance has no vcs info!",
+                vcs_info_path.display()
+            );
+        }
+        self.pack_context.provenance = Some(provenance);
+        Ok(())
+    }
+
+    fn pack_context(mut self) -> Result<PackageContext, CrateSpecError> {
+        self.cmd_cargo_package()?;
+        self.read_crate()?;
+        self.read_provenance()?;
+        Ok(self.pack_context)
     }
+}
+
+///CARGO_TARGET_DIR if set, relative to the crate cargo package runs in,
+///otherwise target of the workspace root
+fn target_dir(
+    cargo_target_dir: Option<OsString>,
+    crate_path: &Path,
+    workspace_dir: &Path,
+) -> PathBuf {
+    match cargo_target_dir {
+        Some(dir) if !dir.is_empty() => crate_path.join(dir),
+        _ => workspace_dir.join("target"),
+    }
+}
 
-    fn pack_context(mut self) -> PackageContext {
-        self.cmd_cargo_package();
-        self.read_crate();
-        self.pack_context
+///SOURCE_DATE_EPOCH if set, so that packing is reproducible
+fn pack_timestamp() -> Result<u64, CrateSpecError> {
+    match env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => epoch
+            .trim()
+            .parse()
+            .map_err(|_| CrateSpecError::Format(format!("SOURCE_DATE_EPOCH {} not valid", epoch))),
+        Err(_) => Ok(SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| CrateSpecError::Io(io::Error::other(e)))?
+            .as_secs()),
     }
 }
 
-pub fn pack_context(path: &str) -> PackageContext {
+///{"git": {"sha1": "...", "dirty": true}, "path_in_vcs": "..."}
+fn read_vcs_info(json: &[u8], provenance: &mut ProvenanceInfo) -> Result<(), CrateSpecError> {
+    let vcs_info: serde_json::Value = serde_json::from_slice(json)
+        .map_err(|e| CrateSpecError::Format(format!(".cargo_vcs_info.json not right - {}", e)))?;
+    provenance.vcs_commit = vcs_info["git"]["sha1"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    provenance.vcs_dirty = vcs_info["git"]["dirty"].as_bool().unwrap_or(false);
+    provenance.vcs_path = vcs_info["path_in_vcs"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    Ok(())
+}
+
+pub fn pack_context(path: &str) -> Result<PackageContext, CrateSpecError> {
     Packing::new(path).pack_context()
 }
 
@@ -91,6 +194,58 @@ pub fn pack_name(pack: &PackageContext) -> String {
 
 #[test]
 fn test_cmd_cargo_package() {
-    let pac = pack_context("../crate-spec");
+    let pac = pack_context("../crate-spec").unwrap();
     println!("{:#?}", pac);
 }
+
+#[test]
+fn test_run_cmd() {
+    assert!(run_cmd("cargo", ["--version"].to_vec(), None)
+        .unwrap()
+        .starts_with("cargo"));
+    //a missing toolchain is an error rather than a panic
+    assert!(matches!(
+        run_cmd("crate-spec-no-such-cmd", [].to_vec(), None),
+        Err(CrateSpecError::Io(_))
+    ));
+    assert!(matches!(
+        run_cmd("cargo", ["no-such-subcommand"].to_vec(), None),
+        Err(CrateSpecError::Io(_))
+    ));
+}
+
+#[test]
+fn test_read_vcs_info() {
+    let mut provenance = ProvenanceInfo::default();
+    read_vcs_info(
+        br#"{"git": {"sha1": "8ce0486a1f", "dirty": true}, "path_in_vcs": "crates/spec"}"#,
+        &mut provenance,
+    )
+    .unwrap();
+    assert_eq!("8ce0486a1f", provenance.vcs_commit);
+    assert!(provenance.vcs_dirty);
+    assert_eq!("crates/spec", provenance.vcs_path);
+    assert!(read_vcs_info(b"not json", &mut provenance).is_err());
+}
+
+#[test]
+fn test_target_dir() {
+    let crate_path = Path::new("ws/member");
+    let workspace_dir = Path::new("/ws");
+    assert_eq!(
+        PathBuf::from("/ws/target"),
+        target_dir(None, crate_path, workspace_dir)
+    );
+    assert_eq!(
+        PathBuf::from("/ws/target"),
+        target_dir(Some(OsString::new()), crate_path, workspace_dir)
+    );
+    assert_eq!(
+        PathBuf::from("ws/member/out"),
+        target_dir(Some(OsString::from("out")), crate_path, workspace_dir)
+    );
+    assert_eq!(
+        PathBuf::from("/tmp/out"),
+        target_dir(Some(OsString::from("/tmp/out")), crate_path, workspace_dir)
+    );
+}
@@ -1,5 +1,8 @@
 use crate_spec::utils::context::PackageContext;
+use crate_spec::utils::error::CrateSpecError;
 use crate_spec::utils::pkcs::PKCS;
+use crate_spec::utils::verify::VerificationPolicy;
+use memmap2::Mmap;
 use std::fs;
 use std::path::PathBuf;
 use std::str::FromStr;
@@ -7,6 +10,8 @@ use std::str::FromStr;
 struct Unpacking {
     file_path: PathBuf,
     cas_path: Vec<String>,
+    keyring_path: Vec<String>,
+    policy: VerificationPolicy,
 }
 
 impl Unpacking {
@@ -14,20 +19,27 @@ impl Unpacking {
         Unpacking {
             file_path: PathBuf::from_str(path).unwrap(),
             cas_path: Vec::new(),
+            keyring_path: Vec::new(),
+            policy: VerificationPolicy::new(),
         }
     }
 
-    pub fn add_ca_from_file(&mut self, path: &str) {
-        let file_path = fs::canonicalize(PathBuf::from_str(path).unwrap()).unwrap();
+    pub fn add_ca_from_file(&mut self, path: &str) -> Result<(), CrateSpecError> {
+        let file_path = fs::canonicalize(PathBuf::from_str(path).unwrap())?;
         self.cas_path.push(file_path.to_str().unwrap().to_string());
+        Ok(())
     }
 
-    pub fn unpack_context(self) -> Result<PackageContext, String> {
+    pub fn unpack_context(self) -> Result<PackageContext, CrateSpecError> {
         let mut package_context_new = PackageContext::new();
-        package_context_new.set_root_cas_bin(PKCS::root_ca_bins(self.cas_path));
-        let bin = fs::read(self.file_path).unwrap();
-        let (_crate_package_new, _str_table) =
-            package_context_new.decode_from_crate_package(bin.as_slice())?;
+        package_context_new.set_root_cas_bin(PKCS::root_ca_bins(self.cas_path)?);
+        package_context_new.set_keyring_bin(PKCS::keyring_bins(self.keyring_path)?);
+        package_context_new.set_verification_policy(self.policy);
+        let file = fs::File::open(self.file_path)?;
+        //SAFETY: file is only read while it is mapped
+        let bin = unsafe { Mmap::map(&file)? };
+        let crate_package = package_context_new.decode_from_crate_package_ref(&bin)?;
+        package_context_new.crate_binary.bytes = crate_package.crate_binary()?.into_owned();
         Ok(package_context_new)
     }
 }
@@ -35,11 +47,15 @@ impl Unpacking {
 pub fn unpack_context(
     file_path: &str,
     cas_path: Vec<String>,
-) -> Result<PackageContext, String> {
+    keyring_path: Vec<String>,
+    policy: VerificationPolicy,
+) -> Result<PackageContext, CrateSpecError> {
     let mut unpack = Unpacking::new(file_path);
-    cas_path
-        .iter()
-        .for_each(|ca_path| unpack.add_ca_from_file(ca_path.as_str()));
+    unpack.keyring_path = keyring_path;
+    unpack.policy = policy;
+    for ca_path in cas_path.iter() {
+        unpack.add_ca_from_file(ca_path.as_str())?;
+    }
     unpack.unpack_context()
 }
 
@@ -47,23 +63,30 @@ pub fn unpack_context(
 fn test_unpack() {
     use crate::pack::pack_context;
     use crate_spec::utils::context::SIGTYPE;
-    let mut pack_context = pack_context("../crate-spec");
+    let mut pack_context = pack_context("../crate-spec").unwrap();
     fn sign() -> PKCS {
         let mut pkcs1 = PKCS::new();
-        pkcs1.load_from_file_writer(
-            "test/cert.pem".to_string(),
-            "test/key.pem".to_string(),
-            ["test/root-ca.pem".to_string()].to_vec(),
-        );
+        pkcs1
+            .load_from_file_writer(
+                "test/cert.pem".to_string(),
+                "test/key.pem".to_string(),
+                ["test/root-ca.pem".to_string()].to_vec(),
+            )
+            .unwrap();
         pkcs1
     }
     pack_context.add_sig(sign(), SIGTYPE::CRATEBIN);
 
-    let (_, _, bin) = pack_context.encode_to_crate_package();
+    let (_, _, bin) = pack_context.encode_to_crate_package().unwrap();
     fs::write(PathBuf::from_str("test/crate-spec.cra").unwrap(), bin).unwrap();
 
-    let pack_context_decode =
-        unpack_context("test/crate-spec.cra", vec!["test/root-ca.pem".to_string()]);
+    let pack_context_decode = unpack_context(
+        "test/crate-spec.cra",
+        vec!["test/root-ca.pem".to_string()],
+        vec![],
+        VerificationPolicy::new(),
+    )
+    .unwrap();
 
     assert_eq!(pack_context_decode.pack_info, pack_context.pack_info);
     assert_eq!(pack_context_decode.dep_infos, pack_context.dep_infos);
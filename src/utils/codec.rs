@@ -0,0 +1,100 @@
+//!section payload codec
+use crate::utils::error::CrateSpecError;
+use crate::utils::package::Uchar;
+use std::io::{Read, Write};
+
+pub const CODEC_NONE: Uchar = 0;
+pub const CODEC_ZSTD: Uchar = 1;
+pub const CODEC_XZ: Uchar = 2;
+
+const ZSTD_LEVEL: i32 = 19;
+const XZ_LEVEL: u32 = 6;
+
+pub enum CODECTYPE {
+    NONE,
+    ZSTD,
+    XZ,
+}
+
+impl CODECTYPE {
+    pub fn id(&self) -> Uchar {
+        match self {
+            CODECTYPE::NONE => CODEC_NONE,
+            CODECTYPE::ZSTD => CODEC_ZSTD,
+            CODECTYPE::XZ => CODEC_XZ,
+        }
+    }
+}
+
+fn codec_err(codec: Uchar, e: std::io::Error) -> CrateSpecError {
+    CrateSpecError::Format(format!("codec {} not right - {}", codec, e))
+}
+
+pub fn encode_by_codec(codec: Uchar, bytes: &[u8]) -> Result<Vec<u8>, CrateSpecError> {
+    match codec {
+        CODEC_NONE => Ok(bytes.to_vec()),
+        CODEC_ZSTD => zstd::stream::encode_all(bytes, ZSTD_LEVEL).map_err(|e| codec_err(codec, e)),
+        CODEC_XZ => {
+            let mut encoder = xz2::write::XzEncoder::new(vec![], XZ_LEVEL);
+            encoder.write_all(bytes).map_err(|e| codec_err(codec, e))?;
+            encoder.finish().map_err(|e| codec_err(codec, e))
+        }
+        _ => Err(CrateSpecError::Format(format!(
+            "codec {} not supported",
+            codec
+        ))),
+    }
+}
+
+///output larger than max_size is rejected before it is all allocated
+pub fn decode_by_codec(
+    codec: Uchar,
+    bytes: &[u8],
+    max_size: usize,
+) -> Result<Vec<u8>, CrateSpecError> {
+    let mut buf = vec![];
+    let limit = max_size as u64 + 1;
+    match codec {
+        CODEC_NONE => buf.extend_from_slice(bytes),
+        CODEC_ZSTD => {
+            zstd::stream::read::Decoder::new(bytes)
+                .map_err(|e| codec_err(codec, e))?
+                .take(limit)
+                .read_to_end(&mut buf)
+                .map_err(|e| codec_err(codec, e))?;
+        }
+        CODEC_XZ => {
+            xz2::read::XzDecoder::new(bytes)
+                .take(limit)
+                .read_to_end(&mut buf)
+                .map_err(|e| codec_err(codec, e))?;
+        }
+        _ => {
+            return Err(CrateSpecError::Format(format!(
+                "codec {} not supported",
+                codec
+            )))
+        }
+    }
+    if buf.len() > max_size {
+        return Err(CrateSpecError::Format(format!(
+            "codec {} output exceeds limit {}",
+            codec, max_size
+        )));
+    }
+    Ok(buf)
+}
+
+#[test]
+fn test_codec() {
+    let bytes = [7; 1000].to_vec();
+    for codec in [CODECTYPE::NONE, CODECTYPE::ZSTD, CODECTYPE::XZ] {
+        let encoded = encode_by_codec(codec.id(), &bytes).unwrap();
+        assert_eq!(bytes, decode_by_codec(codec.id(), &encoded, 1000).unwrap());
+        if codec.id() != CODEC_NONE {
+            assert!(decode_by_codec(codec.id(), &encoded, 999).is_err());
+        }
+    }
+    assert!(decode_by_codec(CODEC_ZSTD, &bytes, 1000).is_err());
+    assert!(encode_by_codec(9, &bytes).is_err());
+}
@@ -1,8 +1,13 @@
+use crate::utils::codec::{CODECTYPE, CODEC_NONE};
+use crate::utils::error::CrateSpecError;
+use crate::utils::package::gen_bincode::checked_range;
 use crate::utils::package::{
-    CrateBinarySection, DepTableEntry, LenArrayType, PackageSection, RawArrayType,
-    SigStructureSection, Size, Type,
+    is_supported_align, is_supported_version, CrateBinarySection, DecodeLimits, DepTableEntry,
+    FeatureTableEntry, LenArrayType, LockTableEntry, PackageSection, ProvenanceSection,
+    RawArrayType, SigStructureSection, Size, Type, Uchar, UnknownSection, CRATEVERSION, NO_ALIGN,
 };
-use crate::utils::pkcs::PKCS;
+use crate::utils::pkcs::{SignerInfo, PKCS};
+use crate::utils::verify::{VerificationPolicy, VerificationReport};
 use std::collections::HashMap;
 
 pub const NOT_SIG_NUM: usize = 3;
@@ -10,6 +15,8 @@ pub const NOT_SIG_NUM: usize = 3;
 pub enum SIGTYPE {
     FILE,
     CRATEBIN,
+    ///signs crate_header.merkle_root, since version 2
+    MERKLE,
 }
 
 pub enum DATASECTIONTYPE {
@@ -17,6 +24,9 @@ pub enum DATASECTIONTYPE {
     DEPTABLE = 1,
     CRATEBIN = 3,
     SIGSTRUCTURE = 4,
+    FEATURETABLE = 5,
+    LOCKTABLE = 6,
+    PROVENANCE = 7,
 }
 
 ///package context contains package's self and dependency package info
@@ -24,9 +34,34 @@ pub enum DATASECTIONTYPE {
 pub struct PackageContext {
     pub pack_info: PackageInfo,
     pub dep_infos: Vec<DepInfo>,
+    ///names of dependencies Cargo.toml declares but dep_infos can not represent,
+    ///written since version 8
+    pub unrepresented_deps: Vec<String>,
+    ///[features], the feature table section is only written if it is not empty
+    pub feature_infos: Vec<FeatureInfo>,
+    ///[[package]] of Cargo.lock, the lock table section is only written if it is not empty
+    pub lock_infos: Vec<LockInfo>,
+    ///environment the package was packed in, the provenance section is only written if set
+    pub provenance: Option<ProvenanceInfo>,
     pub crate_binary: CrateBinary,
     pub sigs: Vec<SigInfo>,
     pub root_cas: Vec<Vec<u8>>,
+    ///pem public keys raw signatures are verified against
+    pub keyring: Vec<Vec<u8>>,
+    ///sections this reader does not understand, written back unchanged
+    pub unknown_sections: Vec<UnknownSectionInfo>,
+    ///format version to write, or the version that was read
+    pub crate_version: Uchar,
+    ///codec applied to each section type when encoding
+    pub section_codecs: HashMap<Type, Uchar>,
+    ///limits checked when decoding
+    pub decode_limits: DecodeLimits,
+    ///alignment of section index and sections, since version 3
+    pub section_align: Uchar,
+    ///decides whether the signatures read are enough to accept the package
+    pub verification_policy: VerificationPolicy,
+    ///result of every signature, filled when decoding
+    pub verification_report: VerificationReport,
 }
 
 impl PackageContext {
@@ -35,11 +70,61 @@ impl PackageContext {
             pack_info: PackageInfo::default(),
             crate_binary: CrateBinary::new(),
             dep_infos: vec![],
+            unrepresented_deps: vec![],
+            feature_infos: vec![],
+            lock_infos: vec![],
+            provenance: None,
             sigs: vec![],
             root_cas: vec![],
+            keyring: vec![],
+            unknown_sections: vec![],
+            crate_version: CRATEVERSION,
+            section_codecs: HashMap::new(),
+            decode_limits: DecodeLimits::new(),
+            section_align: NO_ALIGN,
+            verification_policy: VerificationPolicy::new(),
+            verification_report: VerificationReport::new(),
         }
     }
 
+    pub fn set_decode_limits(&mut self, limits: DecodeLimits) {
+        self.decode_limits = limits;
+    }
+
+    pub fn set_verification_policy(&mut self, policy: VerificationPolicy) {
+        self.verification_policy = policy;
+    }
+
+    ///encode payload of a section type by codec, signature sections are never encoded
+    pub fn set_section_codec(&mut self, typ: DATASECTIONTYPE, codec: CODECTYPE) {
+        self.section_codecs.insert(typ as Type, codec.id());
+    }
+
+    pub fn section_codec(&self, typ: Type) -> Uchar {
+        *self.section_codecs.get(&typ).unwrap_or(&CODEC_NONE)
+    }
+
+    ///target an older format version when encoding
+    pub fn set_crate_version(&mut self, c_version: Uchar) -> Result<(), CrateSpecError> {
+        if !is_supported_version(c_version) {
+            return Err(CrateSpecError::UnsupportedVersion(c_version));
+        }
+        self.crate_version = c_version;
+        Ok(())
+    }
+
+    ///pad section index entries and sections to 4 or 8 bytes, 1 packs them
+    pub fn set_section_align(&mut self, align: Uchar) -> Result<(), CrateSpecError> {
+        if !is_supported_align(align) {
+            return Err(CrateSpecError::Format(format!(
+                "section align {} not supported",
+                align
+            )));
+        }
+        self.section_align = align;
+        Ok(())
+    }
+
     pub fn set_package_info(
         &mut self,
         name: String,
@@ -52,6 +137,7 @@ impl PackageContext {
             version,
             license,
             authors,
+            ..Default::default()
         }
     }
 
@@ -67,10 +153,20 @@ impl PackageContext {
             ver_req,
             src,
             src_platform,
-            dump: true,
+            ..Default::default()
         });
     }
 
+    pub fn add_feature_info(&mut self, name: String, activations: Vec<String>) {
+        self.feature_infos.push(FeatureInfo { name, activations });
+    }
+
+    pub fn feature_info(&self, name: &str) -> Option<&FeatureInfo> {
+        self.feature_infos
+            .iter()
+            .find(|feature| feature.name == name)
+    }
+
     pub fn dep_num(&self) -> usize {
         self.dep_infos.len()
     }
@@ -81,6 +177,7 @@ impl PackageContext {
         match sign_type {
             SIGTYPE::FILE => siginfo.typ = 0,
             SIGTYPE::CRATEBIN => siginfo.typ = 1,
+            SIGTYPE::MERKLE => siginfo.typ = 2,
         }
         self.sigs.push(siginfo);
         self.sigs.len() - 1
@@ -98,6 +195,10 @@ impl PackageContext {
         self.root_cas.push(root_ca);
     }
 
+    pub fn set_keyring_bin(&mut self, keyring_bins: Vec<Vec<u8>>) {
+        self.keyring = keyring_bins;
+    }
+
     pub fn add_crate_bin(&mut self, bin: Vec<u8>) {
         let mut c = CrateBinary::new();
         c.set_bin(bin);
@@ -111,13 +212,25 @@ impl Default for PackageContext {
     }
 }
 
-///package's info
+///package's info, empty string means the key is not set
 #[derive(Debug, PartialEq)]
 pub struct PackageInfo {
     pub name: String,
     pub version: String,
     pub license: String,
     pub authors: Vec<String>,
+    ///extended metadata, only written since version 4
+    pub description: String,
+    pub homepage: String,
+    pub repository: String,
+    pub documentation: String,
+    pub readme: String,
+    pub keywords: Vec<String>,
+    pub categories: Vec<String>,
+    pub edition: String,
+    pub rust_version: String,
+    pub links: String,
+    pub license_file: String,
 }
 
 impl Default for PackageInfo {
@@ -127,10 +240,48 @@ impl Default for PackageInfo {
             version: "".to_string(),
             license: "".to_string(),
             authors: vec![],
+            description: "".to_string(),
+            homepage: "".to_string(),
+            repository: "".to_string(),
+            documentation: "".to_string(),
+            readme: "".to_string(),
+            keywords: vec![],
+            categories: vec![],
+            edition: "".to_string(),
+            rust_version: "".to_string(),
+            links: "".to_string(),
+            license_file: "".to_string(),
         }
     }
 }
 
+pub(crate) fn insert_strs(strs: &[String], str_table: &mut StringTable) -> LenArrayType<u32> {
+    let mut strs_off = vec![];
+    strs.iter().for_each(|st| {
+        strs_off.push(str_table.insert_str(st.clone()));
+    });
+    LenArrayType::copy_from_vec(&strs_off)
+}
+
+pub(crate) fn strs_by_off(
+    strs_off: &LenArrayType<u32>,
+    str_table: &StringTable,
+) -> Result<Vec<String>, CrateSpecError> {
+    strs_off
+        .arr
+        .iter()
+        .map(|st_off| str_table.str_by_off(st_off))
+        .collect()
+}
+
+fn uchar_to_bool(val: Uchar, name: &str) -> Result<bool, CrateSpecError> {
+    match val {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(CrateSpecError::Format(format!("{} not valid!", name))),
+    }
+}
+
 impl PackageInfo {
     pub fn new(name: String, version: String, lisense: String, authors: Vec<String>) -> Self {
         Self {
@@ -138,28 +289,77 @@ impl PackageInfo {
             version,
             license: lisense,
             authors,
+            ..Default::default()
         }
     }
 
+    ///any of the fields version 4 adds is set
+    pub fn has_extended_metadata(&self) -> bool {
+        [
+            &self.description,
+            &self.homepage,
+            &self.repository,
+            &self.documentation,
+            &self.readme,
+            &self.edition,
+            &self.rust_version,
+            &self.links,
+            &self.license_file,
+        ]
+        .iter()
+        .any(|field| !field.is_empty())
+            || !self.keywords.is_empty()
+            || !self.categories.is_empty()
+    }
+
+    ///extended metadata is not written if ps.c_version is older than 4,
+    ///encoders refuse such a package before
     pub fn write_to_package_section(&self, ps: &mut PackageSection, str_table: &mut StringTable) {
         ps.pkg_name = str_table.insert_str(self.name.clone());
         ps.pkg_version = str_table.insert_str(self.version.clone());
         ps.pkg_license = str_table.insert_str(self.license.clone());
-        let mut authors_off = vec![];
-        self.authors.iter().for_each(|author| {
-            authors_off.push(str_table.insert_str(author.clone()));
-        });
-        ps.pkg_authors = LenArrayType::copy_from_vec(&authors_off);
-    }
-
-    pub fn read_from_package_section(&mut self, ps: &PackageSection, str_table: &StringTable) {
-        self.name = str_table.str_by_off(&ps.pkg_name);
-        self.version = str_table.str_by_off(&ps.pkg_version);
-        self.license = str_table.str_by_off(&ps.pkg_license);
-        let authors_off = ps.pkg_authors.to_vec();
-        authors_off.iter().for_each(|author_off| {
-            self.authors.push(str_table.str_by_off(author_off));
-        });
+        ps.pkg_authors = insert_strs(&self.authors, str_table);
+        if ps.c_version < 4 {
+            return;
+        }
+        ps.pkg_description = str_table.insert_str(self.description.clone());
+        ps.pkg_homepage = str_table.insert_str(self.homepage.clone());
+        ps.pkg_repository = str_table.insert_str(self.repository.clone());
+        ps.pkg_documentation = str_table.insert_str(self.documentation.clone());
+        ps.pkg_readme = str_table.insert_str(self.readme.clone());
+        ps.pkg_keywords = insert_strs(&self.keywords, str_table);
+        ps.pkg_categories = insert_strs(&self.categories, str_table);
+        ps.pkg_edition = str_table.insert_str(self.edition.clone());
+        ps.pkg_rust_version = str_table.insert_str(self.rust_version.clone());
+        ps.pkg_links = str_table.insert_str(self.links.clone());
+        ps.pkg_license_file = str_table.insert_str(self.license_file.clone());
+    }
+
+    pub fn read_from_package_section(
+        &mut self,
+        ps: &PackageSection,
+        str_table: &StringTable,
+    ) -> Result<(), CrateSpecError> {
+        self.name = str_table.str_by_off(&ps.pkg_name)?;
+        self.version = str_table.str_by_off(&ps.pkg_version)?;
+        self.license = str_table.str_by_off(&ps.pkg_license)?;
+        self.authors
+            .extend(strs_by_off(&ps.pkg_authors, str_table)?);
+        if ps.c_version < 4 {
+            return Ok(());
+        }
+        self.description = str_table.str_by_off(&ps.pkg_description)?;
+        self.homepage = str_table.str_by_off(&ps.pkg_homepage)?;
+        self.repository = str_table.str_by_off(&ps.pkg_repository)?;
+        self.documentation = str_table.str_by_off(&ps.pkg_documentation)?;
+        self.readme = str_table.str_by_off(&ps.pkg_readme)?;
+        self.keywords = strs_by_off(&ps.pkg_keywords, str_table)?;
+        self.categories = strs_by_off(&ps.pkg_categories, str_table)?;
+        self.edition = str_table.str_by_off(&ps.pkg_edition)?;
+        self.rust_version = str_table.str_by_off(&ps.pkg_rust_version)?;
+        self.links = str_table.str_by_off(&ps.pkg_links)?;
+        self.license_file = str_table.str_by_off(&ps.pkg_license_file)?;
+        Ok(())
     }
 }
 
@@ -170,6 +370,12 @@ pub struct DepInfo {
     pub ver_req: String,
     pub src: SrcTypePath,
     pub src_platform: String,
+    pub kind: DepKind,
+    pub optional: bool,
+    pub default_features: bool,
+    pub features: Vec<String>,
+    ///real package name if the dependency is renamed, empty otherwise
+    pub package: String,
     ///only dump dependency that can be written to crate dependency table section
     pub dump: bool,
 }
@@ -181,11 +387,38 @@ impl Default for DepInfo {
             ver_req: "default".to_string(),
             src: SrcTypePath::CratesIo,
             src_platform: "default".to_string(),
+            kind: DepKind::Normal,
+            optional: false,
+            default_features: true,
+            features: vec![],
+            package: "".to_string(),
             dump: true,
         }
     }
 }
 
+///table the dependency comes from
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DepKind {
+    ///[dependencies]
+    Normal = 0,
+    ///[dev-dependencies]
+    Dev = 1,
+    ///[build-dependencies]
+    Build = 2,
+}
+
+impl DepKind {
+    pub fn from_type(typ: Type) -> Result<Self, CrateSpecError> {
+        match typ {
+            0 => Ok(DepKind::Normal),
+            1 => Ok(DepKind::Dev),
+            2 => Ok(DepKind::Build),
+            _ => Err(CrateSpecError::Format("dep_kind not valid!".to_string())),
+        }
+    }
+}
+
 impl DepInfo {
     pub fn new(
         name: String,
@@ -200,9 +433,18 @@ impl DepInfo {
             src,
             src_platform,
             dump,
+            ..Default::default()
         }
     }
 
+    ///optional, default-features, features and package can only be written since version 6
+    pub fn has_attributes(&self) -> bool {
+        self.optional
+            || !self.default_features
+            || !self.features.is_empty()
+            || !self.package.is_empty()
+    }
+
     pub fn write_to_dep_table_entry(&self, dte: &mut DepTableEntry, str_table: &mut StringTable) {
         dte.dep_name = str_table.insert_str(self.name.clone());
         dte.dep_verreq = str_table.insert_str(self.ver_req.clone());
@@ -211,9 +453,25 @@ impl DepInfo {
                 dte.dep_srctype = 0;
                 dte.dep_srcpath = str_table.insert_str("".to_string());
             }
-            SrcTypePath::Git(str) => {
-                dte.dep_srctype = 1;
-                dte.dep_srcpath = str_table.insert_str(str.clone());
+            SrcTypePath::Git { url, reference } => {
+                dte.dep_srcpath = str_table.insert_str(url.clone());
+                match reference {
+                    GitReference::DefaultBranch => {
+                        dte.dep_srctype = 1;
+                    }
+                    GitReference::Rev(str) => {
+                        dte.dep_srctype = 6;
+                        dte.dep_srcref = str_table.insert_str(str.clone());
+                    }
+                    GitReference::Branch(str) => {
+                        dte.dep_srctype = 7;
+                        dte.dep_srcref = str_table.insert_str(str.clone());
+                    }
+                    GitReference::Tag(str) => {
+                        dte.dep_srctype = 8;
+                        dte.dep_srcref = str_table.insert_str(str.clone());
+                    }
+                }
             }
             SrcTypePath::Url(str) => {
                 dte.dep_srctype = 2;
@@ -227,35 +485,236 @@ impl DepInfo {
                 dte.dep_srctype = 4;
                 dte.dep_srcpath = str_table.insert_str(str.clone());
             }
+            SrcTypePath::Path(str) => {
+                dte.dep_srctype = 5;
+                dte.dep_srcpath = str_table.insert_str(str.clone());
+            }
         }
         dte.dep_platform = str_table.insert_str(self.src_platform.to_string());
+        dte.dep_kind = self.kind as Type;
+        dte.dep_optional = self.optional as Uchar;
+        dte.dep_default_features = self.default_features as Uchar;
+        dte.dep_features = insert_strs(&self.features, str_table);
+        dte.dep_package = str_table.insert_str(self.package.to_string());
     }
 
-    pub fn read_from_dep_table_entry(&mut self, dte: &DepTableEntry, str_table: &StringTable) {
+    pub fn read_from_dep_table_entry(
+        &mut self,
+        dte: &DepTableEntry,
+        str_table: &StringTable,
+    ) -> Result<(), CrateSpecError> {
         self.dump = true;
-        self.name = str_table.str_by_off(&dte.dep_name);
-        self.ver_req = str_table.str_by_off(&dte.dep_verreq);
+        self.name = str_table.str_by_off(&dte.dep_name)?;
+        self.ver_req = str_table.str_by_off(&dte.dep_verreq)?;
         match dte.dep_srctype {
             0 => {
                 self.src = SrcTypePath::CratesIo;
             }
             1 => {
-                self.src = SrcTypePath::Git(str_table.str_by_off(&dte.dep_srcpath));
+                self.src = SrcTypePath::Git {
+                    url: str_table.str_by_off(&dte.dep_srcpath)?,
+                    reference: GitReference::DefaultBranch,
+                };
             }
             2 => {
-                self.src = SrcTypePath::Url(str_table.str_by_off(&dte.dep_srcpath));
+                self.src = SrcTypePath::Url(str_table.str_by_off(&dte.dep_srcpath)?);
             }
             3 => {
-                self.src = SrcTypePath::Registry(str_table.str_by_off(&dte.dep_srcpath));
+                self.src = SrcTypePath::Registry(str_table.str_by_off(&dte.dep_srcpath)?);
             }
             4 => {
-                self.src = SrcTypePath::P2p(str_table.str_by_off(&dte.dep_srcpath));
+                self.src = SrcTypePath::P2p(str_table.str_by_off(&dte.dep_srcpath)?);
+            }
+            5 => {
+                self.src = SrcTypePath::Path(str_table.str_by_off(&dte.dep_srcpath)?);
+            }
+            6..=8 => {
+                let reference = str_table.str_by_off(&dte.dep_srcref)?;
+                self.src = SrcTypePath::Git {
+                    url: str_table.str_by_off(&dte.dep_srcpath)?,
+                    reference: match dte.dep_srctype {
+                        6 => GitReference::Rev(reference),
+                        7 => GitReference::Branch(reference),
+                        _ => GitReference::Tag(reference),
+                    },
+                };
             }
             _ => {
-                panic!("dep_srctype not valid!")
+                return Err(CrateSpecError::Format("dep_srctype not valid!".to_string()));
             }
         }
-        self.src_platform = str_table.str_by_off(&dte.dep_platform);
+        self.src_platform = str_table.str_by_off(&dte.dep_platform)?;
+        self.kind = DepKind::from_type(dte.dep_kind)?;
+        self.optional = uchar_to_bool(dte.dep_optional, "dep_optional")?;
+        self.default_features = uchar_to_bool(dte.dep_default_features, "dep_default_features")?;
+        self.features = strs_by_off(&dte.dep_features, str_table)?;
+        self.package = str_table.str_by_off(&dte.dep_package)?;
+        Ok(())
+    }
+}
+
+///feature and what it enables
+#[derive(Debug, PartialEq, Default)]
+pub struct FeatureInfo {
+    pub name: String,
+    pub activations: Vec<String>,
+}
+
+///one activation of a feature
+#[derive(Debug, PartialEq)]
+pub enum FeatureActivation {
+    ///`feat`, another feature of this package
+    Feature(String),
+    ///`dep:pkg`, an optional dependency
+    Dep(String),
+    ///`pkg/feat`, or `pkg?/feat` when weak, a feature of a dependency
+    DepFeature {
+        dep: String,
+        feature: String,
+        weak: bool,
+    },
+}
+
+impl FeatureActivation {
+    pub fn parse(activation: &str) -> Self {
+        if let Some(dep) = activation.strip_prefix("dep:") {
+            return FeatureActivation::Dep(dep.to_string());
+        }
+        match activation.split_once('/') {
+            Some((dep, feature)) => match dep.strip_suffix('?') {
+                Some(dep) => FeatureActivation::DepFeature {
+                    dep: dep.to_string(),
+                    feature: feature.to_string(),
+                    weak: true,
+                },
+                None => FeatureActivation::DepFeature {
+                    dep: dep.to_string(),
+                    feature: feature.to_string(),
+                    weak: false,
+                },
+            },
+            None => FeatureActivation::Feature(activation.to_string()),
+        }
+    }
+}
+
+impl FeatureInfo {
+    pub fn new(name: String, activations: Vec<String>) -> Self {
+        Self { name, activations }
+    }
+
+    pub fn parsed_activations(&self) -> Vec<FeatureActivation> {
+        self.activations
+            .iter()
+            .map(|activation| FeatureActivation::parse(activation))
+            .collect()
+    }
+
+    pub fn write_to_feature_table_entry(
+        &self,
+        fte: &mut FeatureTableEntry,
+        str_table: &mut StringTable,
+    ) {
+        fte.feat_name = str_table.insert_str(self.name.clone());
+        fte.feat_activations = insert_strs(&self.activations, str_table);
+    }
+
+    pub fn read_from_feature_table_entry(
+        &mut self,
+        fte: &FeatureTableEntry,
+        str_table: &StringTable,
+    ) -> Result<(), CrateSpecError> {
+        self.name = str_table.str_by_off(&fte.feat_name)?;
+        self.activations = strs_by_off(&fte.feat_activations, str_table)?;
+        Ok(())
+    }
+}
+
+///resolved package of Cargo.lock
+#[derive(Debug, PartialEq, Default)]
+pub struct LockInfo {
+    pub name: String,
+    pub version: String,
+    ///empty for path dependencies and the root package
+    pub source: String,
+    ///empty if Cargo.lock has no checksum
+    pub checksum: String,
+    ///"name", "name version" or "name version (source)" as written in Cargo.lock
+    pub dependencies: Vec<String>,
+}
+
+impl LockInfo {
+    pub fn write_to_lock_table_entry(&self, lte: &mut LockTableEntry, str_table: &mut StringTable) {
+        lte.lock_name = str_table.insert_str(self.name.clone());
+        lte.lock_version = str_table.insert_str(self.version.clone());
+        lte.lock_source = str_table.insert_str(self.source.clone());
+        lte.lock_checksum = str_table.insert_str(self.checksum.clone());
+        lte.lock_deps = insert_strs(&self.dependencies, str_table);
+    }
+
+    pub fn read_from_lock_table_entry(
+        &mut self,
+        lte: &LockTableEntry,
+        str_table: &StringTable,
+    ) -> Result<(), CrateSpecError> {
+        self.name = str_table.str_by_off(&lte.lock_name)?;
+        self.version = str_table.str_by_off(&lte.lock_version)?;
+        self.source = str_table.str_by_off(&lte.lock_source)?;
+        self.checksum = str_table.str_by_off(&lte.lock_checksum)?;
+        self.dependencies = strs_by_off(&lte.lock_deps, str_table)?;
+        Ok(())
+    }
+}
+
+///environment the package was packed in, empty string means unknown
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct ProvenanceInfo {
+    ///`rustc --version`
+    pub rustc_version: String,
+    ///`cargo --version`
+    pub cargo_version: String,
+    pub host: String,
+    ///seconds since unix epoch, SOURCE_DATE_EPOCH if set
+    pub timestamp: u64,
+    pub crate_spec_version: String,
+    ///git sha1 of .cargo_vcs_info.json
+    pub vcs_commit: String,
+    ///working tree had uncommitted changes
+    pub vcs_dirty: bool,
+    ///crate directory relative to the repository root
+    pub vcs_path: String,
+}
+
+impl ProvenanceInfo {
+    pub fn write_to_provenance_section(
+        &self,
+        ps: &mut ProvenanceSection,
+        str_table: &mut StringTable,
+    ) {
+        ps.prov_rustc = str_table.insert_str(self.rustc_version.clone());
+        ps.prov_cargo = str_table.insert_str(self.cargo_version.clone());
+        ps.prov_host = str_table.insert_str(self.host.clone());
+        ps.prov_timestamp = self.timestamp;
+        ps.prov_crate_spec = str_table.insert_str(self.crate_spec_version.clone());
+        ps.prov_vcs_commit = str_table.insert_str(self.vcs_commit.clone());
+        ps.prov_vcs_dirty = self.vcs_dirty as Uchar;
+        ps.prov_vcs_path = str_table.insert_str(self.vcs_path.clone());
+    }
+
+    pub fn read_from_provenance_section(
+        &mut self,
+        ps: &ProvenanceSection,
+        str_table: &StringTable,
+    ) -> Result<(), CrateSpecError> {
+        self.rustc_version = str_table.str_by_off(&ps.prov_rustc)?;
+        self.cargo_version = str_table.str_by_off(&ps.prov_cargo)?;
+        self.host = str_table.str_by_off(&ps.prov_host)?;
+        self.timestamp = ps.prov_timestamp;
+        self.crate_spec_version = str_table.str_by_off(&ps.prov_crate_spec)?;
+        self.vcs_commit = str_table.str_by_off(&ps.prov_vcs_commit)?;
+        self.vcs_dirty = uchar_to_bool(ps.prov_vcs_dirty, "prov_vcs_dirty")?;
+        self.vcs_path = str_table.str_by_off(&ps.prov_vcs_path)?;
+        Ok(())
     }
 }
 
@@ -263,10 +722,34 @@ impl DepInfo {
 #[derive(Debug, PartialEq)]
 pub enum SrcTypePath {
     CratesIo,
-    Git(String),
+    Git {
+        url: String,
+        reference: GitReference,
+    },
     Url(String),
     Registry(String),
     P2p(String),
+    Path(String),
+}
+
+impl SrcTypePath {
+    ///path and pinned git sources can only be written since version 7
+    pub fn is_extended(&self) -> bool {
+        match self {
+            SrcTypePath::Path(_) => true,
+            SrcTypePath::Git { reference, .. } => *reference != GitReference::DefaultBranch,
+            _ => false,
+        }
+    }
+}
+
+///git reference a git dependency is pinned to
+#[derive(Debug, PartialEq, Clone)]
+pub enum GitReference {
+    DefaultBranch,
+    Rev(String),
+    Branch(String),
+    Tag(String),
 }
 
 ///StringTable
@@ -294,16 +777,16 @@ impl StringTable {
     }
 
     pub fn insert_str(&mut self, st: String) -> u32 {
-        return if self.str2off.contains_key(&st) {
+        if self.str2off.contains_key(&st) {
             *self.str2off.get(&st).unwrap()
         } else {
-            let st_len = st.as_bytes().len() as u32;
+            let st_len = st.len() as u32;
             let ret_val = self.total_bytes;
             self.str2off.insert(st.clone(), self.total_bytes);
             self.off2str.insert(self.total_bytes, st.clone());
             self.total_bytes += 4 + st_len;
             ret_val
-        };
+        }
     }
 
     pub fn contains_str(&self, st: &String) -> bool {
@@ -314,8 +797,14 @@ impl StringTable {
         *self.str2off.get(st).unwrap()
     }
 
-    pub fn str_by_off(&self, off: &u32) -> String {
-        self.off2str.get(off).unwrap().clone()
+    pub fn str_by_off(&self, off: &u32) -> Result<String, CrateSpecError> {
+        match self.off2str.get(off) {
+            Some(st) => Ok(st.clone()),
+            None => Err(CrateSpecError::Format(format!(
+                "string offset {} not found in string table",
+                off
+            ))),
+        }
     }
 
     ///dump string table to bytes
@@ -333,18 +822,26 @@ impl StringTable {
     }
 
     ///parse string table from bytes
-    pub fn read_bytes(&mut self, bytes: &[u8]) {
+    pub fn read_bytes(&mut self, bytes: &[u8]) -> Result<(), CrateSpecError> {
+        let strtable_err =
+            || CrateSpecError::Format("file format not right! - strtable".to_string());
         let mut i = 0;
         while i < bytes.len() {
-            let mut len_bytes: [u8; 4] = [0; 4];
-            len_bytes.copy_from_slice(bytes[i..i + 4].as_ref());
+            let off = u32::try_from(i).map_err(|_| strtable_err())?;
+            let len_range = checked_range(i, 4, bytes.len()).ok_or_else(strtable_err)?;
+            let mut len_bytes = [0; 4];
+            len_bytes.copy_from_slice(&bytes[len_range.clone()]);
             let len = u32::from_le_bytes(len_bytes) as usize;
-            let st = String::from_utf8(bytes[i + 4..i + 4 + len].to_vec()).unwrap();
-            self.str2off.insert(st.clone(), i as u32);
-            self.off2str.insert(i as u32, st);
-            i += 4 + len;
-            self.total_bytes = i as u32;
+            let st_range =
+                checked_range(len_range.end, len, bytes.len()).ok_or_else(strtable_err)?;
+            let st =
+                String::from_utf8(bytes[st_range.clone()].to_vec()).map_err(|_| strtable_err())?;
+            self.str2off.insert(st.clone(), off);
+            self.off2str.insert(off, st);
+            i = st_range.end;
+            self.total_bytes = u32::try_from(i).map_err(|_| strtable_err())?;
         }
+        Ok(())
     }
 }
 
@@ -378,12 +875,46 @@ impl CrateBinary {
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub struct UnknownSectionInfo {
+    pub typ: Type,
+    pub codec: Uchar,
+    pub bytes: Vec<u8>,
+}
+
+impl UnknownSectionInfo {
+    pub fn new(typ: Type, bytes: Vec<u8>) -> Self {
+        Self {
+            typ,
+            codec: CODEC_NONE,
+            bytes,
+        }
+    }
+
+    pub fn write_to_unknown_section(&self, us: &mut UnknownSection) {
+        us.typ = self.typ;
+        us.codec = self.codec;
+        us.bytes = RawArrayType::from_vec(self.bytes.clone());
+    }
+
+    pub fn read_from_unknown_section(&mut self, us: &UnknownSection) {
+        self.typ = us.typ;
+        self.codec = us.codec;
+        self.bytes = us.bytes.arr.clone();
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct SigInfo {
     pub typ: u32,
+    ///sig scheme, taken from pkcs when encoding
+    pub scheme: Uchar,
     pub size: usize,
     pub bin: Vec<u8>,
     pub pkcs: PKCS,
+    ///filled when decoding if the signature can be parsed,
+    ///whether it is trusted is given by verification_report
+    pub signer: Option<SignerInfo>,
 }
 
 impl Default for SigInfo {
@@ -396,15 +927,18 @@ impl SigInfo {
     pub fn new() -> Self {
         SigInfo {
             typ: 0,
+            scheme: 0,
             size: 0,
             bin: vec![],
             pkcs: PKCS::new(),
+            signer: None,
         }
     }
 
     pub fn read_from_sig_structure_section(&mut self, sig: &SigStructureSection) {
         //FIXME current it's not right
         self.typ = sig.sigstruct_type as u32;
+        self.scheme = sig.sigstruct_scheme;
         self.size = sig.sigstruct_size as usize;
         self.bin = sig.sigstruct_sig.arr.clone();
     }
@@ -412,6 +946,7 @@ impl SigInfo {
     pub fn write_to_sig_structure_section(&self, sig: &mut SigStructureSection) {
         //FIXME current it's not right
         sig.sigstruct_type = self.typ as Type;
+        sig.sigstruct_scheme = self.scheme;
         sig.sigstruct_size = self.size as Size;
         sig.sigstruct_sig = RawArrayType::from_vec(self.bin.clone());
     }
@@ -1,186 +1,746 @@
-use crate::utils::context::{DepInfo, PackageContext, SigInfo, StringTable};
+use crate::utils::codec::{decode_by_codec, CODEC_NONE};
+use crate::utils::context::{
+    strs_by_off, DepInfo, FeatureInfo, LockInfo, PackageContext, ProvenanceInfo, SigInfo,
+    StringTable, UnknownSectionInfo,
+};
+use crate::utils::error::CrateSpecError;
+use crate::utils::merkle::calc_merkle_root;
+use crate::utils::package::gen_bincode::{
+    checked_range, create_bincode_slice_decoder, is_known_section_type, BINCODE_CONFIG,
+};
 use crate::utils::package::{
-    CrateBinarySection, CratePackage, DataSection, DepTableSection, PackageSection, SectionIndex,
-    SigStructureSection, FINGERPRINT_LEN,
+    CrateBinarySection, CrateHeader, CratePackage, CratePackageRef, DataSection, DepTableSection,
+    FeatureTableSection, FingerPrintType, LockTableSection, PackageSection, ProvenanceSection,
+    SectionIndex, SigStructureSection, FINGERPRINT_LEN,
 };
 
-use crate::utils::pkcs::PKCS;
+use crate::utils::package::Uchar;
+use crate::utils::pkcs::{
+    PKCS, SIG_SCHEME_CMS, SIG_SCHEME_ED25519, SIG_SCHEME_P256, SIG_SCHEME_PKCS7,
+};
+use crate::utils::verify::{SigReport, SigStatus, VerificationReport};
+use bincode::Decode;
+use openssl::sha::Sha256;
+use std::borrow::Cow;
+use std::ops::Range;
 
 impl SectionIndex {
-    pub fn section_id_by_type(&self, typ: usize) -> usize {
+    pub fn section_id_by_type(&self, typ: usize) -> Result<usize, CrateSpecError> {
         for (i, entry) in self.entries.arr.iter().enumerate() {
-            if entry.sh_type as usize == typ {
-                return i;
+            if entry.section_type() as usize == typ {
+                return Ok(i);
             }
         }
-        panic!("section typ not found")
+        Err(CrateSpecError::Format(format!(
+            "section typ {} not found",
+            typ
+        )))
+    }
+}
+
+fn check_merkle_root(
+    crate_header: &CrateHeader,
+    string_table: &[u8],
+    section_index: &SectionIndex,
+) -> Result<(), CrateSpecError> {
+    if crate_header.c_version < 2 {
+        return Err(CrateSpecError::Format(format!(
+            "section digest needs version 2, version {} is read",
+            crate_header.c_version
+        )));
     }
+    if calc_merkle_root(crate_header, string_table, section_index)? != crate_header.merkle_root {
+        return Err(CrateSpecError::Corrupted("merkle root".to_string()));
+    }
+    Ok(())
+}
+
+fn check_section_digest(
+    section_index: &SectionIndex,
+    id: usize,
+    section_bin: &[u8],
+) -> Result<(), CrateSpecError> {
+    let entry = section_index
+        .entries
+        .arr
+        .get(id)
+        .ok_or_else(|| CrateSpecError::Format(format!("section {} not found", id)))?;
+    if section_bin.len() != entry.sh_size as usize
+        || PKCS::new().gen_digest_256(section_bin)? != entry.sh_digest
+    {
+        return Err(CrateSpecError::Corrupted(format!("section {}", id)));
+    }
+    Ok(())
+}
+
+///check one signature over actual_digest, pkcs7 against root_cas and raw ones against keyring
+fn verify_sig(
+    scheme: Uchar,
+    sig_bin: &[u8],
+    actual_digest: &[u8],
+    root_cas: &[Vec<u8>],
+    keyring: &[Vec<u8>],
+) -> (SigStatus, String) {
+    let verified = match scheme {
+        SIG_SCHEME_PKCS7 => PKCS::decode_pkcs_bin(sig_bin, root_cas)
+            .map(|expect_digest| actual_digest == expect_digest.as_slice()),
+        SIG_SCHEME_CMS => PKCS::decode_cms_bin(sig_bin, root_cas)
+            .map(|expect_digest| actual_digest == expect_digest.as_slice()),
+        SIG_SCHEME_ED25519 | SIG_SCHEME_P256 => {
+            PKCS::decode_raw_bin(scheme, sig_bin, actual_digest, keyring)
+        }
+        _ => {
+            return (
+                SigStatus::Malformed,
+                format!("sig scheme {} not supported", scheme),
+            )
+        }
+    };
+    match verified {
+        Ok(true) => (SigStatus::Valid, "".to_string()),
+        Ok(false) => (SigStatus::DigestMismatch, "file sig not right".to_string()),
+        Err(CrateSpecError::Trust(reason))
+            if PKCS::is_signer_expired(sig_bin, scheme).unwrap_or(false) =>
+        {
+            (SigStatus::Expired, reason)
+        }
+        Err(CrateSpecError::Trust(reason)) => (SigStatus::Untrusted, reason),
+        Err(e) => (SigStatus::Malformed, e.to_string()),
+    }
+}
+
+///FILE sig is defined over bytes before total size, with sig entries of section index zeroed
+fn sig_zero_range(
+    crate_header: &CrateHeader,
+    section_index: &SectionIndex,
+    len: usize,
+) -> Result<(Range<usize>, usize), CrateSpecError> {
+    let ds_err = || CrateSpecError::Format("file format not right! - ds".to_string());
+    let ds_size = section_index.datasection_size_without_sig()?;
+    let total_size = (crate_header.ds_offset as usize)
+        .checked_add(ds_size)
+        .filter(|total_size| *total_size <= len)
+        .ok_or_else(ds_err)?;
+    let zero_begin = (crate_header.si_offset as usize)
+        .checked_add(section_index.none_sig_size())
+        .ok_or_else(ds_err)?;
+    let zero_range = checked_range(
+        crate_header.si_offset as usize,
+        crate_header.si_size as usize,
+        total_size,
+    )
+    .filter(|si_range| zero_begin <= si_range.end)
+    .map(|si_range| zero_begin..si_range.end)
+    .ok_or_else(ds_err)?;
+    Ok((zero_range, total_size))
 }
 
 impl CratePackage {
-    pub fn data_section_by_id(&self, id: usize) -> &DataSection {
-        &self.data_sections.col.arr[id]
+    pub fn data_section_by_id(&self, id: usize) -> Result<&DataSection, CrateSpecError> {
+        self.data_sections
+            .col
+            .arr
+            .get(id)
+            .ok_or_else(|| CrateSpecError::Format(format!("section {} not found", id)))
     }
 
-    pub fn data_section_by_type(&self, typ: usize) -> &DataSection {
-        self.data_section_by_id(self.section_index.section_id_by_type(typ))
+    pub fn data_section_by_type(&self, typ: usize) -> Result<&DataSection, CrateSpecError> {
+        self.data_section_by_id(self.section_index.section_id_by_type(typ)?)
     }
 
-    pub fn package_section(&self) -> &PackageSection {
+    pub fn package_section(&self) -> Result<&PackageSection, CrateSpecError> {
         //FIXME: 0 should be constant
-        match self.data_section_by_type(0) {
-            DataSection::PackageSection(pak) => pak,
-            _ => {
-                panic!("package section not found!")
-            }
+        match self.data_section_by_type(0)? {
+            DataSection::PackageSection(pak) => Ok(pak),
+            _ => Err(CrateSpecError::Format(
+                "package section not found!".to_string(),
+            )),
         }
     }
 
-    pub fn dep_table_section(&self) -> &DepTableSection {
-        match self.data_section_by_type(1) {
-            DataSection::DepTableSection(dep) => dep,
-            _ => {
-                panic!("dep table section not found!")
-            }
+    pub fn dep_table_section(&self) -> Result<&DepTableSection, CrateSpecError> {
+        match self.data_section_by_type(1)? {
+            DataSection::DepTableSection(dep) => Ok(dep),
+            _ => Err(CrateSpecError::Format(
+                "dep table section not found!".to_string(),
+            )),
         }
     }
 
-    pub fn crate_binary_section(&self) -> &CrateBinarySection {
-        match self.data_section_by_type(3) {
-            DataSection::CrateBinarySection(cra) => cra,
-            _ => {
-                panic!("crate binary section not found!")
-            }
+    ///feature table section is optional
+    pub fn feature_table_section(&self) -> Result<Option<&FeatureTableSection>, CrateSpecError> {
+        if self.section_index.section_id_by_type(5).is_err() {
+            return Ok(None);
+        }
+        match self.data_section_by_type(5)? {
+            DataSection::FeatureTableSection(feature) => Ok(Some(feature)),
+            _ => Err(CrateSpecError::Format(
+                "feature table section not found!".to_string(),
+            )),
         }
     }
 
-    pub fn sig_structure_section(&self, no: usize) -> &SigStructureSection {
-        let base = self.section_index.section_id_by_type(4);
-        match self.data_section_by_id(no + base) {
-            DataSection::SigStructureSection(sig) => sig,
-            _ => {
-                panic!("sig structure section not found!")
-            }
+    ///lock table section is optional
+    pub fn lock_table_section(&self) -> Result<Option<&LockTableSection>, CrateSpecError> {
+        if self.section_index.section_id_by_type(6).is_err() {
+            return Ok(None);
+        }
+        match self.data_section_by_type(6)? {
+            DataSection::LockTableSection(lock) => Ok(Some(lock)),
+            _ => Err(CrateSpecError::Format(
+                "lock table section not found!".to_string(),
+            )),
+        }
+    }
+
+    ///provenance section is optional
+    pub fn provenance_section(&self) -> Result<Option<&ProvenanceSection>, CrateSpecError> {
+        if self.section_index.section_id_by_type(7).is_err() {
+            return Ok(None);
+        }
+        match self.data_section_by_type(7)? {
+            DataSection::ProvenanceSection(provenance) => Ok(Some(provenance)),
+            _ => Err(CrateSpecError::Format(
+                "provenance section not found!".to_string(),
+            )),
+        }
+    }
+
+    pub fn crate_binary_section(&self) -> Result<&CrateBinarySection, CrateSpecError> {
+        match self.data_section_by_type(3)? {
+            DataSection::CrateBinarySection(cra) => Ok(cra),
+            _ => Err(CrateSpecError::Format(
+                "crate binary section not found!".to_string(),
+            )),
+        }
+    }
+
+    ///bytes of a section in the whole file
+    pub fn section_bin<'a>(&self, bin: &'a [u8], id: usize) -> Result<&'a [u8], CrateSpecError> {
+        let entry = self
+            .section_index
+            .entries
+            .arr
+            .get(id)
+            .ok_or_else(|| CrateSpecError::Format(format!("section {} not found", id)))?;
+        (self.crate_header.ds_offset as usize)
+            .checked_add(entry.sh_offset as usize)
+            .and_then(|begin| checked_range(begin, entry.sh_size as usize, bin.len()))
+            .map(|range| &bin[range])
+            .ok_or_else(|| CrateSpecError::Format("file format not right! - ds".to_string()))
+    }
+
+    ///check bytes of one section against its digest,
+    ///header, string table and section index are checked against merkle root first
+    pub fn check_section(&self, id: usize, section_bin: &[u8]) -> Result<(), CrateSpecError> {
+        check_merkle_root(
+            &self.crate_header,
+            self.string_table.arr.as_slice(),
+            &self.section_index,
+        )?;
+        check_section_digest(&self.section_index, id, section_bin)
+    }
+
+    ///check every section of the whole file, does nothing before version 2
+    pub fn check_sections(&self, bin: &[u8]) -> Result<(), CrateSpecError> {
+        if self.crate_header.c_version < 2 {
+            return Ok(());
+        }
+        check_merkle_root(
+            &self.crate_header,
+            self.string_table.arr.as_slice(),
+            &self.section_index,
+        )?;
+        for id in 0..self.section_index.num() {
+            check_section_digest(
+                &self.section_index,
+                id,
+                self.section_bin(bin, id).unwrap_or_default(),
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn sig_structure_section(&self, no: usize) -> Result<&SigStructureSection, CrateSpecError> {
+        let base = self.section_index.section_id_by_type(4)?;
+        match self.data_section_by_id(no + base)? {
+            DataSection::SigStructureSection(sig) => Ok(sig),
+            _ => Err(CrateSpecError::Format(
+                "sig structure section not found!".to_string(),
+            )),
         }
     }
 }
 
+impl<'a> CratePackageRef<'a> {
+    ///bytes of a section in the file
+    pub fn section_bin(&self, id: usize) -> Result<&'a [u8], CrateSpecError> {
+        let entry = self
+            .section_index
+            .entries
+            .arr
+            .get(id)
+            .ok_or_else(|| CrateSpecError::Format(format!("section {} not found", id)))?;
+        (self.crate_header.ds_offset as usize)
+            .checked_add(entry.sh_offset as usize)
+            .and_then(|begin| checked_range(begin, entry.sh_size as usize, self.bin.len()))
+            .map(|range| &self.bin[range])
+            .ok_or_else(|| CrateSpecError::Format("file format not right! - ds".to_string()))
+    }
+
+    ///section bytes decoded by its codec, borrowed when there is no codec
+    pub fn section_payload(&self, id: usize) -> Result<Cow<'a, [u8]>, CrateSpecError> {
+        let section_bin = self.section_bin(id)?;
+        match self.section_index.entries.arr[id].sh_codec {
+            CODEC_NONE => Ok(Cow::Borrowed(section_bin)),
+            codec => Ok(Cow::Owned(decode_by_codec(
+                codec,
+                section_bin,
+                self.limits.max_crate_size,
+            )?)),
+        }
+    }
+
+    fn decode_section<T: Decode>(&self, typ: usize) -> Result<T, CrateSpecError> {
+        let payload = self.section_payload(self.section_index.section_id_by_type(typ)?)?;
+        let (section, _) = bincode::decode_from_slice(&payload, BINCODE_CONFIG)?;
+        Ok(section)
+    }
+
+    pub fn package_section(&self) -> Result<PackageSection, CrateSpecError> {
+        let payload = self.section_payload(self.section_index.section_id_by_type(0)?)?;
+        Ok(PackageSection::decode(
+            &mut create_bincode_slice_decoder(&payload),
+            self.crate_header.c_version,
+        )?)
+    }
+
+    pub fn dep_table_section(&self) -> Result<DepTableSection, CrateSpecError> {
+        let payload = self.section_payload(self.section_index.section_id_by_type(1)?)?;
+        Ok(DepTableSection::decode(
+            &mut create_bincode_slice_decoder(&payload),
+            self.crate_header.c_version,
+        )?)
+    }
+
+    ///feature table section is optional
+    pub fn feature_table_section(&self) -> Result<Option<FeatureTableSection>, CrateSpecError> {
+        if self.section_index.section_id_by_type(5).is_err() {
+            return Ok(None);
+        }
+        Ok(Some(self.decode_section(5)?))
+    }
+
+    ///lock table section is optional
+    pub fn lock_table_section(&self) -> Result<Option<LockTableSection>, CrateSpecError> {
+        if self.section_index.section_id_by_type(6).is_err() {
+            return Ok(None);
+        }
+        Ok(Some(self.decode_section(6)?))
+    }
+
+    ///provenance section is optional
+    pub fn provenance_section(&self) -> Result<Option<ProvenanceSection>, CrateSpecError> {
+        if self.section_index.section_id_by_type(7).is_err() {
+            return Ok(None);
+        }
+        Ok(Some(self.decode_section(7)?))
+    }
+
+    ///crate binary section is its raw bytes
+    pub fn crate_binary(&self) -> Result<Cow<'a, [u8]>, CrateSpecError> {
+        self.section_payload(self.section_index.section_id_by_type(3)?)
+    }
+
+    pub fn sig_structure_section(&self, no: usize) -> Result<SigStructureSection, CrateSpecError> {
+        let base = self.section_index.section_id_by_type(4)?;
+        Ok(SigStructureSection::decode(
+            &mut create_bincode_slice_decoder(self.section_bin(no + base)?),
+            self.crate_header.c_version,
+        )?)
+    }
+
+    ///check every section against its digest, does nothing before version 2
+    pub fn check_sections(&self) -> Result<(), CrateSpecError> {
+        if self.crate_header.c_version < 2 {
+            return Ok(());
+        }
+        check_merkle_root(&self.crate_header, self.string_table, &self.section_index)?;
+        for id in 0..self.section_index.num() {
+            check_section_digest(
+                &self.section_index,
+                id,
+                self.section_bin(id).unwrap_or_default(),
+            )?;
+        }
+        Ok(())
+    }
+
+    ///digest of the bytes FILE sig is defined over, without copying them
+    pub fn digest_before_sig(&self) -> Result<FingerPrintType, CrateSpecError> {
+        let (zero_range, total_size) =
+            sig_zero_range(&self.crate_header, &self.section_index, self.bin.len())?;
+        let (zero_begin, zero_end) = (zero_range.start, zero_range.end);
+        let mut hasher = Sha256::new();
+        hasher.update(&self.bin[..zero_begin]);
+        hasher.update(&vec![0; zero_end - zero_begin]);
+        hasher.update(&self.bin[zero_end..total_size]);
+        Ok(hasher.finish())
+    }
+}
+
 impl PackageContext {
-    pub fn binary_before_sig(&self, crate_package: &CratePackage, bin: &[u8]) -> Vec<u8> {
+    pub fn binary_before_sig(
+        &self,
+        crate_package: &CratePackage,
+        bin: &[u8],
+    ) -> Result<Vec<u8>, CrateSpecError> {
         //FIXME
-        let ds_size = crate_package
-            .section_index
-            .datasection_size_without_sig();
-        let total_size = crate_package.crate_header.ds_offset as usize + ds_size;
-        if crate_package.section_index.sig_num() != self.sigs.len() && !self.sigs.is_empty() {
-            assert_eq!(crate_package.section_index.sig_num(), 0);
+        if crate_package.section_index.sig_num() != self.sigs.len()
+            && !self.sigs.is_empty()
+            && crate_package.section_index.sig_num() != 0
+        {
+            return Err(CrateSpecError::Format(
+                "file format not right! - sig num".to_string(),
+            ));
         }
+        let (zero_range, total_size) = sig_zero_range(
+            &crate_package.crate_header,
+            &crate_package.section_index,
+            bin.len(),
+        )?;
         let mut buf = bin[..total_size].to_vec();
-        let zero_begin = crate_package.crate_header.si_offset as usize
-            + crate_package.section_index.none_sig_size();
-        let zero_end = crate_package.crate_header.si_offset as usize
-            + crate_package.crate_header.si_size as usize;
         //FIXME this is not efficient
-        for i in buf.iter_mut().take(zero_end).skip(zero_begin) {
+        for i in buf.iter_mut().take(zero_range.end).skip(zero_range.start) {
             *i = 0;
         }
 
-        buf
+        Ok(buf)
     }
 
-    pub fn binary_before_digest(&self, bin: &[u8]) -> Vec<u8> {
-        bin[..bin.len() - FINGERPRINT_LEN].to_vec()
+    pub fn binary_before_digest(&self, bin: &[u8]) -> Result<Vec<u8>, CrateSpecError> {
+        if bin.len() < FINGERPRINT_LEN {
+            return Err(CrateSpecError::Format(
+                "file format not right! - fingerprint".to_string(),
+            ));
+        }
+        Ok(bin[..bin.len() - FINGERPRINT_LEN].to_vec())
     }
 
-    fn pack_info(&mut self, crate_package: &CratePackage, str_table: &StringTable) {
+    fn pack_info(
+        &mut self,
+        crate_package: &CratePackage,
+        str_table: &StringTable,
+    ) -> Result<(), CrateSpecError> {
         self.pack_info
-            .read_from_package_section(crate_package.package_section(), str_table);
+            .read_from_package_section(crate_package.package_section()?, str_table)
     }
 
-    fn deps(&mut self, crate_package: &CratePackage, str_table: &StringTable) {
-        for entry in crate_package.dep_table_section().entries.arr.iter() {
+    fn deps(
+        &mut self,
+        dep_table_section: &DepTableSection,
+        str_table: &StringTable,
+    ) -> Result<(), CrateSpecError> {
+        for entry in dep_table_section.entries.arr.iter() {
             let mut dep_info = DepInfo::default();
-            dep_info.read_from_dep_table_entry(entry, str_table);
+            dep_info.read_from_dep_table_entry(entry, str_table)?;
             self.dep_infos.push(dep_info);
         }
+        self.unrepresented_deps = strs_by_off(&dep_table_section.dep_unrepresented, str_table)?;
+        Ok(())
     }
 
-    fn binary(&mut self, crate_package: &CratePackage) {
-        self.crate_binary.bytes = crate_package.crate_binary_section().bin.arr.clone();
+    fn features(
+        &mut self,
+        feature_table_section: Option<&FeatureTableSection>,
+        str_table: &StringTable,
+    ) -> Result<(), CrateSpecError> {
+        for entry in feature_table_section
+            .iter()
+            .flat_map(|fts| fts.entries.arr.iter())
+        {
+            let mut feature_info = FeatureInfo::default();
+            feature_info.read_from_feature_table_entry(entry, str_table)?;
+            self.feature_infos.push(feature_info);
+        }
+        Ok(())
     }
 
-    fn sigs(&mut self, crate_package: &CratePackage) {
+    fn locks(
+        &mut self,
+        lock_table_section: Option<&LockTableSection>,
+        str_table: &StringTable,
+    ) -> Result<(), CrateSpecError> {
+        for entry in lock_table_section
+            .iter()
+            .flat_map(|lts| lts.entries.arr.iter())
+        {
+            let mut lock_info = LockInfo::default();
+            lock_info.read_from_lock_table_entry(entry, str_table)?;
+            self.lock_infos.push(lock_info);
+        }
+        Ok(())
+    }
+
+    fn provenance(
+        &mut self,
+        provenance_section: Option<&ProvenanceSection>,
+        str_table: &StringTable,
+    ) -> Result<(), CrateSpecError> {
+        self.provenance = match provenance_section {
+            None => None,
+            Some(ps) => {
+                let mut provenance = ProvenanceInfo::default();
+                provenance.read_from_provenance_section(ps, str_table)?;
+                Some(provenance)
+            }
+        };
+        Ok(())
+    }
+
+    fn binary(&mut self, crate_package: &CratePackage) -> Result<(), CrateSpecError> {
+        self.crate_binary.bytes = crate_package.crate_binary_section()?.bin.arr.clone();
+        Ok(())
+    }
+
+    fn unknown_sections(&mut self, crate_package: &CratePackage) {
+        for section in crate_package.data_sections.col.arr.iter() {
+            if let DataSection::UnknownSection(unknown) = section {
+                let mut unknown_info = UnknownSectionInfo::new(unknown.typ, vec![]);
+                unknown_info.read_from_unknown_section(unknown);
+                self.unknown_sections.push(unknown_info);
+            }
+        }
+    }
+
+    fn sigs(&mut self, crate_package: &CratePackage) -> Result<(), CrateSpecError> {
         let sig_num = crate_package.section_index.sig_num();
         for no in 0..sig_num {
-            let sig = crate_package.sig_structure_section(no);
+            let sig = crate_package.sig_structure_section(no)?;
             let mut sig_info = SigInfo::new();
             sig_info.bin = sig.sigstruct_sig.arr.clone();
             sig_info.size = sig.sigstruct_size as usize;
             sig_info.typ = sig.sigstruct_type as u32;
+            sig_info.scheme = sig.sigstruct_scheme;
             self.sigs.push(sig_info);
         }
+        Ok(())
+    }
+
+    fn check_fingerprint(&self, bin_all: &[u8]) -> Result<(), CrateSpecError> {
+        if bin_all.len() < FINGERPRINT_LEN {
+            return Err(CrateSpecError::Format(
+                "file format not right! - fingerprint".to_string(),
+            ));
+        }
+        if PKCS::new().gen_digest_256(&bin_all[..bin_all.len() - FINGERPRINT_LEN])?
+            != bin_all[bin_all.len() - FINGERPRINT_LEN..]
+        {
+            return Err(self
+                .corrupted_section(bin_all)
+                .unwrap_or(CrateSpecError::Fingerprint));
+        }
+        Ok(())
     }
 
-    fn check_fingerprint(&self, bin_all: &[u8]) -> bool {
-        PKCS::new().gen_digest_256(&bin_all[..bin_all.len() - FINGERPRINT_LEN])
-            == bin_all[bin_all.len() - FINGERPRINT_LEN..]
+    ///find the corrupted section by section digests,
+    ///only when header, string table and section index are intact
+    fn corrupted_section(&self, bin_all: &[u8]) -> Option<CrateSpecError> {
+        let crate_package =
+            CratePackageRef::decode_from_slice_with_limits(bin_all, &self.decode_limits).ok()?;
+        crate_package.check_sections().err()
     }
 
-    fn check_sigs(&self, crate_package: &CratePackage, bin_all: &[u8]) -> bool {
-        let bin_all = self.binary_before_sig(crate_package, bin_all);
-        let bin_crate = crate_package.crate_binary_section().bin.arr.as_slice();
-        for siginfo in self.sigs.iter() {
-            let actual_digest;
+    ///verify one section fetched on its own, head_bin holds the file up to data sections,
+    ///sig_bin is a MERKLE signature section of the same file
+    pub fn check_section_partial(
+        &self,
+        head_bin: &[u8],
+        section_id: usize,
+        section_bin: &[u8],
+        sig_bin: &[u8],
+    ) -> Result<(), CrateSpecError> {
+        let crate_package = CratePackage::decode_head_from_slice(head_bin)?;
+        crate_package.check_section(section_id, section_bin)?;
+        let sig: SigStructureSection = CratePackage::decode_sig_structure_section(
+            sig_bin,
+            crate_package.crate_header.c_version,
+        )?;
+        if sig.sigstruct_type != 2 {
+            return Err(CrateSpecError::Signature(
+                "sig type is not right!".to_string(),
+            ));
+        }
+        let (status, reason) = verify_sig(
+            sig.sigstruct_scheme,
+            sig.sigstruct_sig.arr.as_slice(),
+            crate_package.crate_header.merkle_root.as_slice(),
+            &self.root_cas,
+            &self.keyring,
+        );
+        let report = SigReport {
+            index: 0,
+            typ: sig.sigstruct_type as u32,
+            status,
+            reason,
+        };
+        if !report.is_valid() {
+            return Err(report.to_error());
+        }
+        Ok(())
+    }
+
+    fn check_sigs(
+        &mut self,
+        crate_package: &CratePackage,
+        bin_all: &[u8],
+    ) -> Result<(), CrateSpecError> {
+        let bin_all = self.binary_before_sig(crate_package, bin_all)?;
+        let file_digest = PKCS::new().gen_digest_256(bin_all.as_slice())?;
+        let crate_digest =
+            PKCS::new().gen_digest_256(crate_package.crate_binary_section()?.bin.arr.as_slice())?;
+        self.check_sig_digests(&crate_package.crate_header, &file_digest, &crate_digest)
+    }
+
+    fn check_sig_digests(
+        &mut self,
+        crate_header: &CrateHeader,
+        file_digest: &[u8],
+        crate_digest: &[u8],
+    ) -> Result<(), CrateSpecError> {
+        let mut report = VerificationReport::new();
+        for (index, siginfo) in self.sigs.iter_mut().enumerate() {
             //FIXME this should be encapsulated as it's used in encode as well
-            match siginfo.typ {
-                0 => {
-                    actual_digest = siginfo.pkcs.gen_digest_256(bin_all.as_slice());
-                }
-                1 => {
-                    actual_digest = siginfo.pkcs.gen_digest_256(bin_crate);
-                }
-                _ => {
-                    panic!("sig type is not right!")
-                }
-            }
-            let expect_digest = PKCS::decode_pkcs_bin(siginfo.bin.as_slice(), &self.root_cas);
-            if actual_digest != expect_digest {
-                return false;
+            let actual_digest = match siginfo.typ {
+                0 => Some(file_digest),
+                1 => Some(crate_digest),
+                //merkle root itself is checked by check_sections
+                2 if crate_header.c_version >= 2 => Some(crate_header.merkle_root.as_slice()),
+                _ => None,
+            };
+            let (status, reason) = match actual_digest {
+                None => (SigStatus::Malformed, "sig type is not right!".to_string()),
+                Some(actual_digest) => verify_sig(
+                    siginfo.scheme,
+                    siginfo.bin.as_slice(),
+                    actual_digest,
+                    &self.root_cas,
+                    &self.keyring,
+                ),
             };
+            if status != SigStatus::Malformed {
+                siginfo.signer = match siginfo.scheme {
+                    SIG_SCHEME_PKCS7 | SIG_SCHEME_CMS => {
+                        PKCS::signer_info(siginfo.bin.as_slice(), siginfo.scheme).ok()
+                    }
+                    _ => PKCS::raw_signer_info(siginfo.bin.as_slice()).ok(),
+                };
+            }
+            report.sigs.push(SigReport {
+                index,
+                typ: siginfo.typ,
+                status,
+                reason,
+            });
         }
-        true
+        self.verification_report = report;
+        self.verification_policy.check(&self.verification_report)
     }
 
     pub fn decode_from_crate_package(
         &mut self,
         bin: &[u8],
-    ) -> Result<(CratePackage, StringTable), String> {
-        if !self.check_fingerprint(bin) {
-            return Err("fingerprint not right".to_string());
-        }
-        let crate_package = CratePackage::decode_from_slice(bin)?;
+    ) -> Result<(CratePackage, StringTable), CrateSpecError> {
+        self.check_fingerprint(bin)?;
+        //a section which does not decode is reported by its digest if it can be
+        let crate_package = CratePackage::decode_from_slice_with_limits(bin, &self.decode_limits)
+            .map_err(|e| self.corrupted_section(bin).unwrap_or(e))?;
+        self.crate_version = crate_package.crate_header.c_version;
+        self.section_align = crate_package.crate_header.c_align;
         let mut str_table = StringTable::new();
-        str_table.read_bytes(crate_package.string_table.arr.as_slice());
-        self.pack_info(&crate_package, &str_table);
-        self.deps(&crate_package, &str_table);
-        self.binary(&crate_package);
-        self.sigs(&crate_package);
-        if !self.check_sigs(&crate_package, bin) {
-            return Err("file sig not right".to_string());
-        }
+        str_table.read_bytes(crate_package.string_table.arr.as_slice())?;
+        self.pack_info(&crate_package, &str_table)?;
+        self.deps(crate_package.dep_table_section()?, &str_table)?;
+        self.features(crate_package.feature_table_section()?, &str_table)?;
+        self.locks(crate_package.lock_table_section()?, &str_table)?;
+        self.provenance(crate_package.provenance_section()?, &str_table)?;
+        self.binary(&crate_package)?;
+        self.unknown_sections(&crate_package);
+        self.sigs(&crate_package)?;
+        self.check_sigs(&crate_package, bin)?;
+        crate_package.check_sections(bin)?;
         Ok((crate_package, str_table))
     }
+
+    ///verify and read metadata without copying sections,
+    ///crate binary is left in the file and is given by CratePackageRef::crate_binary
+    pub fn decode_from_crate_package_ref<'a>(
+        &mut self,
+        bin: &'a [u8],
+    ) -> Result<CratePackageRef<'a>, CrateSpecError> {
+        self.check_fingerprint(bin)?;
+        let crate_package =
+            CratePackageRef::decode_from_slice_with_limits(bin, &self.decode_limits)?;
+        self.crate_version = crate_package.crate_header.c_version;
+        self.section_align = crate_package.crate_header.c_align;
+        //a section which does not decode is reported by its digest if it can be
+        self.read_sections_ref(&crate_package)
+            .map_err(|e| crate_package.check_sections().err().unwrap_or(e))?;
+        let file_digest = if self.sigs.iter().any(|sig| sig.typ == 0) {
+            crate_package.digest_before_sig()?.to_vec()
+        } else {
+            vec![]
+        };
+        let crate_digest = if self.sigs.iter().any(|sig| sig.typ == 1) {
+            PKCS::new().gen_digest_256(&crate_package.crate_binary()?)?
+        } else {
+            vec![]
+        };
+        self.check_sig_digests(&crate_package.crate_header, &file_digest, &crate_digest)?;
+        crate_package.check_sections()?;
+        Ok(crate_package)
+    }
+
+    fn read_sections_ref(&mut self, crate_package: &CratePackageRef) -> Result<(), CrateSpecError> {
+        let mut str_table = StringTable::new();
+        str_table.read_bytes(crate_package.string_table)?;
+        self.pack_info
+            .read_from_package_section(&crate_package.package_section()?, &str_table)?;
+        self.deps(&crate_package.dep_table_section()?, &str_table)?;
+        self.features(crate_package.feature_table_section()?.as_ref(), &str_table)?;
+        self.locks(crate_package.lock_table_section()?.as_ref(), &str_table)?;
+        self.provenance(crate_package.provenance_section()?.as_ref(), &str_table)?;
+        for (id, entry) in crate_package.section_index.entries.arr.iter().enumerate() {
+            if !is_known_section_type(entry.section_type()) {
+                let mut unknown_info =
+                    UnknownSectionInfo::new(entry.sh_type, crate_package.section_bin(id)?.to_vec());
+                unknown_info.codec = entry.sh_codec;
+                self.unknown_sections.push(unknown_info);
+            }
+        }
+        for no in 0..crate_package.section_index.sig_num() {
+            let sig = crate_package.sig_structure_section(no)?;
+            let mut sig_info = SigInfo::new();
+            sig_info.bin = sig.sigstruct_sig.arr;
+            sig_info.size = sig.sigstruct_size as usize;
+            sig_info.typ = sig.sigstruct_type as u32;
+            sig_info.scheme = sig.sigstruct_scheme;
+            self.sigs.push(sig_info);
+        }
+        Ok(())
+    }
 }
 
 #[test]
 fn test_encode_decode() {
-    use crate::utils::context::{PackageInfo, SrcTypePath, SIGTYPE};
+    use crate::utils::context::{GitReference, PackageInfo, SrcTypePath, SIGTYPE};
     fn pack_info() -> PackageInfo {
         PackageInfo {
             name: "rust-crate".to_string(),
             version: "1.0.0".to_string(),
             license: "MIT".to_string(),
             authors: vec!["shuibing".to_string(), "rust".to_string()],
+            ..Default::default()
         }
     }
 
@@ -190,7 +750,7 @@ fn test_encode_decode() {
             ver_req: "1.0.0".to_string(),
             src: SrcTypePath::CratesIo,
             src_platform: "ALL".to_string(),
-            dump: true,
+            ..Default::default()
         }
     }
 
@@ -198,9 +758,12 @@ fn test_encode_decode() {
         DepInfo {
             name: "crate-spec".to_string(),
             ver_req: ">=0.8.0".to_string(),
-            src: SrcTypePath::Git("http://git.com".to_string()),
+            src: SrcTypePath::Git {
+                url: "http://git.com".to_string(),
+                reference: GitReference::DefaultBranch,
+            },
             src_platform: "windows".to_string(),
-            dump: true,
+            ..Default::default()
         }
     }
 
@@ -210,11 +773,13 @@ fn test_encode_decode() {
 
     fn sign() -> PKCS {
         let mut pkcs1 = PKCS::new();
-        pkcs1.load_from_file_writer(
-            "test/cert.pem".to_string(),
-            "test/key.pem".to_string(),
-            ["test/root-ca.pem".to_string()].to_vec(),
-        );
+        pkcs1
+            .load_from_file_writer(
+                "test/cert.pem".to_string(),
+                "test/key.pem".to_string(),
+                ["test/root-ca.pem".to_string()].to_vec(),
+            )
+            .unwrap();
         pkcs1
     }
 
@@ -227,12 +792,11 @@ fn test_encode_decode() {
     package_context.add_sig(sign(), SIGTYPE::CRATEBIN);
     package_context.add_sig(sign(), SIGTYPE::FILE);
 
-    let (_crate_package, _str_table, bin) = package_context.encode_to_crate_package();
+    let (_crate_package, _str_table, bin) = package_context.encode_to_crate_package().unwrap();
 
     let mut package_context_new = PackageContext::new();
-    package_context_new.set_root_cas_bin(PKCS::root_ca_bins(
-        ["test/root-ca.pem".to_string()].to_vec(),
-    ));
+    package_context_new
+        .set_root_cas_bin(PKCS::root_ca_bins(["test/root-ca.pem".to_string()].to_vec()).unwrap());
     let (_crate_package_new, _str_table) = package_context_new
         .decode_from_crate_package(bin.as_slice())
         .unwrap();
@@ -242,3 +806,906 @@ fn test_encode_decode() {
     assert_eq!(dep_info2(), package_context_new.dep_infos[1]);
     assert_eq!(crate_binary(), package_context_new.crate_binary.bytes);
 }
+
+#[test]
+fn test_package_metadata() {
+    use crate::utils::context::PackageInfo;
+    use crate::utils::error::CrateSpecError;
+    use crate::utils::fixture;
+
+    fn pack_info() -> PackageInfo {
+        PackageInfo {
+            name: "rust-crate".to_string(),
+            version: "1.0.0".to_string(),
+            license: "MIT".to_string(),
+            authors: vec!["shuibing".to_string(), "rust".to_string()],
+            description: "crate package spec".to_string(),
+            homepage: "https://crate-spec.rs".to_string(),
+            repository: "https://github.com/open-rust-initiative/crate-spec".to_string(),
+            documentation: "https://docs.rs/crate-spec".to_string(),
+            readme: "README.md".to_string(),
+            keywords: vec!["crate".to_string(), "sign".to_string()],
+            categories: vec!["encoding".to_string()],
+            edition: "2021".to_string(),
+            rust_version: "1.70".to_string(),
+            links: "ssl".to_string(),
+            license_file: "LICENSE".to_string(),
+        }
+    }
+
+    let mut package_context = fixture::package_context(100);
+    package_context.pack_info = pack_info();
+    assert_eq!(
+        pack_info(),
+        fixture::round_trip(&mut package_context).pack_info
+    );
+
+    //version 4 adds the metadata, it is refused before
+    package_context.set_crate_version(3).unwrap();
+    assert!(matches!(
+        package_context.encode_to_crate_package(),
+        Err(CrateSpecError::Format(_))
+    ));
+    assert!(matches!(
+        package_context.encode_to_writer(&mut std::io::Cursor::new(vec![])),
+        Err(CrateSpecError::Format(_))
+    ));
+
+    //older package sections hold name, version, license and authors
+    package_context.pack_info = PackageInfo::new(
+        "rust-crate".to_string(),
+        "1.0.0".to_string(),
+        "MIT".to_string(),
+        vec!["shuibing".to_string(), "rust".to_string()],
+    );
+    assert_eq!(
+        PackageInfo {
+            name: "rust-crate".to_string(),
+            version: "1.0.0".to_string(),
+            license: "MIT".to_string(),
+            authors: vec!["shuibing".to_string(), "rust".to_string()],
+            ..Default::default()
+        },
+        fixture::round_trip(&mut package_context).pack_info
+    );
+}
+
+#[test]
+fn test_feature_table() {
+    use crate::utils::fixture;
+
+    //no feature, no feature table section
+    let mut package_context = fixture::package_context(100);
+    let (crate_package, _, _) = package_context.encode_to_crate_package().unwrap();
+    assert!(crate_package.feature_table_section().unwrap().is_none());
+
+    package_context.add_feature_info("default".to_string(), vec!["std".to_string()]);
+    package_context.add_feature_info("std".to_string(), vec![]);
+    package_context.add_feature_info(
+        "serde".to_string(),
+        vec!["dep:serde".to_string(), "toml?/preserve_order".to_string()],
+    );
+    let (_, _, bin) = package_context.encode_to_crate_package().unwrap();
+    let mut package_context_new = fixture::verifier();
+    package_context_new
+        .decode_from_crate_package(bin.as_slice())
+        .unwrap();
+    assert_eq!(
+        package_context.feature_infos,
+        package_context_new.feature_infos
+    );
+    let mut package_context_ref = fixture::verifier();
+    package_context_ref
+        .decode_from_crate_package_ref(bin.as_slice())
+        .unwrap();
+    assert_eq!(
+        package_context.feature_infos,
+        package_context_ref.feature_infos
+    );
+
+    //feature table is an optional section, it needs no format version
+    package_context.set_crate_version(0).unwrap();
+    let package_context_v0 = fixture::round_trip(&mut package_context);
+    assert_eq!(
+        package_context.feature_infos,
+        package_context_v0.feature_infos
+    );
+}
+
+#[test]
+fn test_dep_kind() {
+    use crate::utils::context::DepKind;
+    use crate::utils::error::CrateSpecError;
+    use crate::utils::fixture;
+
+    fn dep_info(name: &str, kind: DepKind) -> DepInfo {
+        DepInfo {
+            name: name.to_string(),
+            ver_req: "1".to_string(),
+            kind,
+            ..Default::default()
+        }
+    }
+
+    let mut package_context = fixture::package_context(100);
+    package_context.dep_infos = vec![
+        dep_info("toml", DepKind::Normal),
+        dep_info("tempfile", DepKind::Dev),
+        dep_info("cc", DepKind::Build),
+    ];
+    let dep_infos = fixture::round_trip(&mut package_context).dep_infos;
+    assert_eq!(package_context.dep_infos, dep_infos);
+
+    //version 5 adds dep_kind, dev and build dependencies are refused before
+    package_context.set_crate_version(4).unwrap();
+    assert!(matches!(
+        package_context.encode_to_crate_package(),
+        Err(CrateSpecError::Format(msg)) if msg.contains("tempfile")
+    ));
+    package_context
+        .dep_infos
+        .retain(|dep_info| dep_info.kind == DepKind::Normal);
+    assert_eq!(
+        [dep_info("toml", DepKind::Normal)].as_slice(),
+        fixture::round_trip(&mut package_context).dep_infos
+    );
+}
+
+#[test]
+fn test_dep_platform() {
+    use crate::utils::fixture;
+
+    let mut package_context = fixture::package_context(100);
+    for platform in ["", "cfg(windows)", "x86_64-unknown-linux-gnu"] {
+        package_context.dep_infos.push(DepInfo {
+            name: format!("dep-{}", platform),
+            ver_req: "1".to_string(),
+            src_platform: platform.to_string(),
+            ..Default::default()
+        });
+    }
+    let dep_infos = fixture::round_trip(&mut package_context).dep_infos;
+    assert_eq!(package_context.dep_infos, dep_infos);
+
+    //dep_platform is in the dependency table since version 0
+    package_context.set_crate_version(0).unwrap();
+    let dep_infos = fixture::round_trip(&mut package_context).dep_infos;
+    assert_eq!(package_context.dep_infos, dep_infos);
+}
+
+#[test]
+fn test_dep_attributes() {
+    use crate::utils::error::CrateSpecError;
+    use crate::utils::fixture;
+
+    let mut package_context = fixture::package_context(100);
+    package_context.dep_infos = vec![
+        DepInfo {
+            name: "toml".to_string(),
+            ver_req: "0.7".to_string(),
+            ..Default::default()
+        },
+        DepInfo {
+            name: "rand_old".to_string(),
+            ver_req: "0.7".to_string(),
+            optional: true,
+            default_features: false,
+            features: vec!["std".to_string(), "small_rng".to_string()],
+            package: "rand".to_string(),
+            ..Default::default()
+        },
+    ];
+    let dep_infos = fixture::round_trip(&mut package_context).dep_infos;
+    assert_eq!(package_context.dep_infos, dep_infos);
+
+    //version 6 adds the attributes, a dependency which has any is refused before
+    package_context.set_crate_version(5).unwrap();
+    assert!(matches!(
+        package_context.encode_to_crate_package(),
+        Err(CrateSpecError::Format(_))
+    ));
+    package_context.dep_infos.pop();
+    let dep_infos = fixture::round_trip(&mut package_context).dep_infos;
+    assert_eq!(package_context.dep_infos, dep_infos);
+}
+
+#[test]
+fn test_dep_source() {
+    use crate::utils::context::{GitReference, SrcTypePath};
+    use crate::utils::error::CrateSpecError;
+    use crate::utils::fixture;
+
+    fn dep_info(name: &str, src: SrcTypePath) -> DepInfo {
+        DepInfo {
+            name: name.to_string(),
+            ver_req: "1".to_string(),
+            src,
+            ..Default::default()
+        }
+    }
+    fn git(reference: GitReference) -> SrcTypePath {
+        SrcTypePath::Git {
+            url: "https://github.com/rust-lang/regex.git".to_string(),
+            reference,
+        }
+    }
+
+    fn plain_deps() -> Vec<DepInfo> {
+        vec![
+            dep_info("toml", SrcTypePath::CratesIo),
+            dep_info("log", git(GitReference::DefaultBranch)),
+        ]
+    }
+
+    for extended_dep in [
+        dep_info("utils", SrcTypePath::Path("../utils".to_string())),
+        dep_info("regex", git(GitReference::Rev("9f9f693".to_string()))),
+        dep_info("regex", git(GitReference::Branch("main".to_string()))),
+        dep_info("regex", git(GitReference::Tag("1.10.0".to_string()))),
+    ] {
+        let mut package_context = fixture::package_context(100);
+        package_context.dep_infos = plain_deps();
+        package_context.dep_infos.push(extended_dep);
+        let dep_infos = fixture::round_trip(&mut package_context).dep_infos;
+        assert_eq!(package_context.dep_infos, dep_infos);
+
+        //version 7 adds path and pinned git sources, they are refused before
+        package_context.set_crate_version(6).unwrap();
+        assert!(matches!(
+            package_context.encode_to_crate_package(),
+            Err(CrateSpecError::Format(_))
+        ));
+        package_context.dep_infos.pop();
+        let dep_infos = fixture::round_trip(&mut package_context).dep_infos;
+        assert_eq!(plain_deps(), dep_infos);
+    }
+}
+
+#[test]
+fn test_unrepresented_deps() {
+    use crate::utils::error::CrateSpecError;
+    use crate::utils::fixture;
+
+    let mut package_context = fixture::package_context(100);
+    package_context.unrepresented_deps = vec!["tool".to_string(), "plugin".to_string()];
+    let unrepresented_deps = fixture::round_trip(&mut package_context).unrepresented_deps;
+    assert_eq!(package_context.unrepresented_deps, unrepresented_deps);
+
+    //version 8 adds dep_unrepresented, they are refused before
+    package_context.set_crate_version(7).unwrap();
+    assert!(matches!(
+        package_context.encode_to_crate_package(),
+        Err(CrateSpecError::Format(_))
+    ));
+    package_context.unrepresented_deps.clear();
+    assert!(fixture::round_trip(&mut package_context)
+        .unrepresented_deps
+        .is_empty());
+}
+
+#[test]
+fn test_lock_table() {
+    use crate::utils::fixture;
+    use crate::utils::from_toml::CrateLock;
+
+    fn has_lock_table(crate_package: &CratePackage) -> bool {
+        crate_package
+            .section_index
+            .entries
+            .arr
+            .iter()
+            .any(|entry| entry.sh_type == 6)
+    }
+
+    //no lock infos, no lock table section
+    let mut package_context = fixture::package_context(100);
+    let (crate_package, _, _) = package_context.encode_to_crate_package().unwrap();
+    assert!(!has_lock_table(&crate_package));
+    assert!(fixture::round_trip(&mut package_context)
+        .lock_infos
+        .is_empty());
+
+    CrateLock::from_file("test/test.lock".to_string())
+        .unwrap()
+        .write_info_to_package_context(&mut package_context)
+        .unwrap();
+    let (crate_package, _, bin) = package_context.encode_to_crate_package().unwrap();
+    assert!(has_lock_table(&crate_package));
+    let lock_infos = fixture::round_trip(&mut package_context).lock_infos;
+    assert_eq!(package_context.lock_infos, lock_infos);
+    let mut package_context_ref = fixture::verifier();
+    package_context_ref
+        .decode_from_crate_package_ref(bin.as_slice())
+        .unwrap();
+    assert_eq!(package_context.lock_infos, package_context_ref.lock_infos);
+
+    //lock table is an optional section, every version can carry it
+    package_context.set_crate_version(0).unwrap();
+    let lock_infos = fixture::round_trip(&mut package_context).lock_infos;
+    assert_eq!(package_context.lock_infos, lock_infos);
+}
+
+#[test]
+fn test_provenance() {
+    use crate::utils::context::ProvenanceInfo;
+    use crate::utils::fixture;
+
+    let mut package_context = fixture::package_context(100);
+    assert!(fixture::round_trip(&mut package_context)
+        .provenance
+        .is_none());
+
+    package_context.provenance = Some(ProvenanceInfo {
+        rustc_version: "rustc 1.75.0 (82e1608df 2023-12-21)".to_string(),
+        cargo_version: "cargo 1.75.0 (1d8b05cdd 2023-11-20)".to_string(),
+        host: "x86_64-unknown-linux-gnu".to_string(),
+        timestamp: 1700000000,
+        crate_spec_version: "0.1.0".to_string(),
+        vcs_commit: "8ce0486a1f".to_string(),
+        vcs_dirty: true,
+        vcs_path: "".to_string(),
+    });
+    let provenance = fixture::round_trip(&mut package_context).provenance;
+    assert_eq!(package_context.provenance, provenance);
+    let (_, _, bin) = package_context.encode_to_crate_package().unwrap();
+    let mut package_context_ref = fixture::verifier();
+    package_context_ref
+        .decode_from_crate_package_ref(bin.as_slice())
+        .unwrap();
+    assert_eq!(package_context.provenance, package_context_ref.provenance);
+
+    //unknown vcs info is kept as empty strings
+    package_context.provenance = Some(ProvenanceInfo {
+        timestamp: 1700000000,
+        ..Default::default()
+    });
+    package_context.set_crate_version(0).unwrap();
+    let provenance = fixture::round_trip(&mut package_context).provenance;
+    assert_eq!(package_context.provenance, provenance);
+}
+
+#[test]
+fn test_signer_info() {
+    use crate::utils::context::SIGTYPE;
+    use crate::utils::fixture;
+    use crate::utils::pkcs::SIGSCHEME;
+    use crate::utils::verify::VerificationPolicy;
+
+    let cert = openssl::x509::X509::from_pem(&std::fs::read("test/cert.pem").unwrap()).unwrap();
+    let fingerprint: String = cert
+        .digest(openssl::hash::MessageDigest::sha256())
+        .unwrap()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    //cms is written since version 9, pkcs7 before, both report their signer
+    for (scheme, c_version) in [(SIGSCHEME::CMS, 9), (SIGSCHEME::PKCS7, 8)] {
+        let mut pkcs = fixture::cert_signer();
+        pkcs.set_cert_scheme(scheme).unwrap();
+        let mut package_context = fixture::package_context(100);
+        package_context.add_sig(pkcs, SIGTYPE::CRATEBIN);
+        package_context.set_crate_version(c_version).unwrap();
+        let sigs = fixture::round_trip(&mut package_context).sigs;
+        let signer = sigs[0].signer.as_ref().unwrap();
+        assert!(signer.subject.ends_with("CN=foobar.com"));
+        assert_eq!(fingerprint, signer.fingerprint);
+        assert_eq!(cert.not_after().to_string(), signer.not_after);
+    }
+
+    //signer of an untrusted signature is still reported
+    let mut package_context = fixture::package_context(100);
+    package_context.add_sig(
+        fixture::signer("test/chain-cert.pem", "test/chain-key.pem"),
+        SIGTYPE::CRATEBIN,
+    );
+    let (_, _, bin) = package_context.encode_to_crate_package().unwrap();
+    let mut package_context_new = fixture::verifier();
+    package_context_new.set_verification_policy(VerificationPolicy::at_least(0));
+    package_context_new
+        .decode_from_crate_package(bin.as_slice())
+        .unwrap();
+    assert_eq!(
+        SigStatus::Untrusted,
+        package_context_new.verification_report.sigs[0].status
+    );
+    assert!(package_context_new.sigs[0].signer.is_some());
+}
+
+#[test]
+fn test_decode_malformed() {
+    use crate::utils::error::CrateSpecError;
+    use crate::utils::fixture;
+
+    let mut package_context = fixture::package_context(100);
+    let (_, _, bin) = package_context.encode_to_crate_package().unwrap();
+
+    let mut short = PackageContext::new();
+    assert!(matches!(
+        short.decode_from_crate_package(&bin[..10]),
+        Err(CrateSpecError::Format(_))
+    ));
+
+    let mut tampered_bin = bin.clone();
+    tampered_bin[20] ^= 0xff;
+    let mut tampered = PackageContext::new();
+    assert!(matches!(
+        tampered.decode_from_crate_package(&tampered_bin),
+        Err(CrateSpecError::Fingerprint)
+    ));
+
+    let mut bad_magic_bin = bin[..bin.len() - FINGERPRINT_LEN].to_vec();
+    bad_magic_bin[0] = 0;
+    let fp = PKCS::new().gen_digest_256(&bad_magic_bin).unwrap();
+    bad_magic_bin.extend(fp);
+    let mut bad_magic = PackageContext::new();
+    assert!(matches!(
+        bad_magic.decode_from_crate_package(&bad_magic_bin),
+        Err(CrateSpecError::Format(_))
+    ));
+
+    assert!(matches!(
+        CratePackage::decode_from_slice(&[0x43, 0x52]),
+        Err(CrateSpecError::Format(_))
+    ));
+}
+
+#[test]
+fn test_unknown_section() {
+    use crate::utils::error::CrateSpecError;
+    use crate::utils::fixture;
+    use crate::utils::package::SECTION_CRITICAL_FLAG;
+
+    fn package_context(unknown_typ: u8) -> PackageContext {
+        let mut package_context = fixture::package_context(100);
+        package_context
+            .unknown_sections
+            .push(UnknownSectionInfo::new(
+                unknown_typ,
+                [1, 2, 3, 4, 5].to_vec(),
+            ));
+        package_context
+    }
+
+    //non-critical unknown section is kept and written back unchanged
+    let (_, _, bin) = package_context(100).encode_to_crate_package().unwrap();
+    let mut package_context_new = PackageContext::new();
+    package_context_new
+        .decode_from_crate_package(bin.as_slice())
+        .unwrap();
+    assert_eq!(
+        package_context(100).unknown_sections,
+        package_context_new.unknown_sections
+    );
+    assert_eq!([15; 100].to_vec(), package_context_new.crate_binary.bytes);
+    let (_, _, bin_new) = package_context_new.encode_to_crate_package().unwrap();
+    assert_eq!(bin, bin_new);
+
+    //critical unknown section rejects the file
+    let (_, _, bin) = package_context(100 | SECTION_CRITICAL_FLAG)
+        .encode_to_crate_package()
+        .unwrap();
+    let mut package_context_new = PackageContext::new();
+    assert!(matches!(
+        package_context_new.decode_from_crate_package(bin.as_slice()),
+        Err(CrateSpecError::Format(_))
+    ));
+}
+
+#[test]
+fn test_crate_version() {
+    use crate::utils::error::CrateSpecError;
+    use crate::utils::fixture;
+    use crate::utils::package::{CRATEVERSION, MAGIC_NUMBER_LEN};
+
+    let mut package_context = fixture::package_context(100);
+    assert!(matches!(
+        package_context.set_crate_version(CRATEVERSION + 1),
+        Err(CrateSpecError::UnsupportedVersion(_))
+    ));
+    let (_, _, bin) = package_context.encode_to_crate_package().unwrap();
+    assert_eq!(CRATEVERSION, CratePackage::crate_version(&bin).unwrap());
+
+    let mut package_context_new = PackageContext::new();
+    package_context_new.decode_from_crate_package(&bin).unwrap();
+    assert_eq!(CRATEVERSION, package_context_new.crate_version);
+
+    //file written by a newer writer
+    let mut newer_bin = bin[..bin.len() - FINGERPRINT_LEN].to_vec();
+    newer_bin[MAGIC_NUMBER_LEN] = 200;
+    let fp = PKCS::new().gen_digest_256(&newer_bin).unwrap();
+    newer_bin.extend(fp);
+    let mut package_context_new = PackageContext::new();
+    assert!(matches!(
+        package_context_new.decode_from_crate_package(&newer_bin),
+        Err(CrateSpecError::UnsupportedVersion(200))
+    ));
+}
+
+#[test]
+fn test_section_codec() {
+    use crate::utils::codec::CODECTYPE;
+    use crate::utils::context::{DATASECTIONTYPE, SIGTYPE};
+    use crate::utils::fixture;
+
+    fn package_context(c_version: u8) -> PackageContext {
+        let mut package_context = fixture::package_context(10000);
+        package_context.add_sig(fixture::cert_signer(), SIGTYPE::CRATEBIN);
+        package_context.set_crate_version(c_version).unwrap();
+        package_context.set_section_codec(DATASECTIONTYPE::CRATEBIN, CODECTYPE::ZSTD);
+        package_context.set_section_codec(DATASECTIONTYPE::PACK, CODECTYPE::XZ);
+        package_context
+    }
+
+    let (crate_package, _, bin) = package_context(1).encode_to_crate_package().unwrap();
+    assert!(bin.len() < 10000);
+    assert_eq!(
+        crate_package.section_index.entries.arr[2].sh_codec,
+        CODECTYPE::ZSTD.id()
+    );
+
+    let mut package_context_new = fixture::verifier();
+    package_context_new
+        .decode_from_crate_package(bin.as_slice())
+        .unwrap();
+    assert_eq!(package_context(1).pack_info, package_context_new.pack_info);
+    assert_eq!([15; 10000].to_vec(), package_context_new.crate_binary.bytes);
+
+    //version 0 has no place for the codec
+    assert!(package_context(0).encode_to_crate_package().is_err());
+}
+
+#[test]
+fn test_section_digest() {
+    use crate::utils::context::SIGTYPE;
+    use crate::utils::fixture;
+
+    let mut package_context = fixture::package_context(100);
+    package_context.add_sig(fixture::cert_signer(), SIGTYPE::MERKLE);
+    let (crate_package, _, bin) = package_context.encode_to_crate_package().unwrap();
+
+    fixture::verifier()
+        .decode_from_crate_package(bin.as_slice())
+        .unwrap();
+
+    //transfer error inside crate binary section
+    let crate_bin_id = crate_package.section_index.section_id_by_type(3).unwrap();
+    let crate_bin_begin = crate_package.crate_header.ds_offset as usize
+        + crate_package.section_index.entries.arr[crate_bin_id].sh_offset as usize;
+    let mut broken_bin = bin.clone();
+    broken_bin[crate_bin_begin + 10] ^= 0xff;
+    let mut package_context_new = PackageContext::new();
+    match package_context_new.decode_from_crate_package(broken_bin.as_slice()) {
+        Err(CrateSpecError::Corrupted(part)) => {
+            assert_eq!(format!("section {}", crate_bin_id), part)
+        }
+        _ => panic!("corrupted section not found"),
+    }
+
+    //header, section index, one section and the merkle sig are enough
+    let head_bin = &bin[..crate_package.crate_header.ds_offset as usize];
+    let sig_id = crate_package.section_index.section_id_by_type(4).unwrap();
+    let sig_bin = crate_package.section_bin(&bin, sig_id).unwrap();
+    let package_context_new = fixture::verifier();
+    package_context_new
+        .check_section_partial(
+            head_bin,
+            crate_bin_id,
+            crate_package.section_bin(&bin, crate_bin_id).unwrap(),
+            sig_bin,
+        )
+        .unwrap();
+    assert!(matches!(
+        package_context_new.check_section_partial(
+            head_bin,
+            crate_bin_id,
+            crate_package
+                .section_bin(&broken_bin, crate_bin_id)
+                .unwrap(),
+            sig_bin,
+        ),
+        Err(CrateSpecError::Corrupted(_))
+    ));
+}
+
+#[test]
+fn test_merkle_metadata() {
+    use crate::utils::context::SIGTYPE;
+    use crate::utils::fixture;
+
+    let mut package_context = fixture::package_context(100);
+    package_context.add_sig(fixture::cert_signer(), SIGTYPE::MERKLE);
+    let (crate_package, _, bin) = package_context.encode_to_crate_package().unwrap();
+
+    let head_bin = &bin[..crate_package.crate_header.ds_offset as usize];
+    let crate_bin_id = crate_package.section_index.section_id_by_type(3).unwrap();
+    let crate_bin = crate_package.section_bin(&bin, crate_bin_id).unwrap();
+    let sig_id = crate_package.section_index.section_id_by_type(4).unwrap();
+    let sig_bin = crate_package.section_bin(&bin, sig_id).unwrap();
+    let package_context_new = fixture::verifier();
+    package_context_new
+        .check_section_partial(head_bin, crate_bin_id, crate_bin, sig_bin)
+        .unwrap();
+
+    //section bytes and digest stay the same, only the way to read them changes
+    let tampers: [fn(&mut crate::utils::package::SectionIndexEntry); 2] =
+        [|entry| entry.sh_codec ^= 1, |entry| entry.sh_type = 7];
+    for tamper in tampers {
+        let mut tampered = CratePackage::decode_head_from_slice(head_bin).unwrap();
+        tamper(&mut tampered.section_index.entries.arr[crate_bin_id]);
+        assert!(matches!(
+            package_context_new.check_section_partial(
+                &tampered.encode_head_to_vec(),
+                crate_bin_id,
+                crate_bin,
+                sig_bin,
+            ),
+            Err(CrateSpecError::Corrupted(part)) if part == "merkle root"
+        ));
+
+        //a recomputed merkle root is no longer the signed one
+        tampered.crate_header.merkle_root = tampered.calc_merkle_root().unwrap();
+        assert!(matches!(
+            package_context_new.check_section_partial(
+                &tampered.encode_head_to_vec(),
+                crate_bin_id,
+                crate_bin,
+                sig_bin,
+            ),
+            Err(CrateSpecError::Signature(_))
+        ));
+    }
+}
+
+#[test]
+fn test_decode_ref() {
+    use crate::utils::codec::CODECTYPE;
+    use crate::utils::context::{DATASECTIONTYPE, SIGTYPE};
+    use crate::utils::fixture;
+
+    fn package_context(codec: CODECTYPE) -> PackageContext {
+        let mut package_context = fixture::package_context(1000);
+        package_context
+            .unknown_sections
+            .push(UnknownSectionInfo::new(100, [1, 2, 3].to_vec()));
+        package_context.set_section_codec(DATASECTIONTYPE::CRATEBIN, codec);
+        for sig_type in [SIGTYPE::FILE, SIGTYPE::CRATEBIN, SIGTYPE::MERKLE] {
+            package_context.add_sig(fixture::cert_signer(), sig_type);
+        }
+        package_context
+    }
+
+    for codec in [CODECTYPE::NONE, CODECTYPE::XZ] {
+        let is_borrowed = matches!(codec, CODECTYPE::NONE);
+        let (_, _, bin) = package_context(codec).encode_to_crate_package().unwrap();
+        let mut package_context_new = fixture::verifier();
+        let crate_package = package_context_new
+            .decode_from_crate_package_ref(&bin)
+            .unwrap();
+        let crate_binary = crate_package.crate_binary().unwrap();
+        assert_eq!([15; 1000].as_slice(), crate_binary.as_ref());
+        assert_eq!(is_borrowed, matches!(crate_binary, Cow::Borrowed(_)));
+        assert_eq!(
+            package_context(CODECTYPE::NONE).pack_info,
+            package_context_new.pack_info
+        );
+        assert_eq!(
+            package_context(CODECTYPE::NONE).unknown_sections,
+            package_context_new.unknown_sections
+        );
+        assert_eq!(3, package_context_new.sigs.len());
+    }
+
+    let (_, _, mut bin) = package_context(CODECTYPE::NONE)
+        .encode_to_crate_package()
+        .unwrap();
+    let last = bin.len() - FINGERPRINT_LEN - 1;
+    bin[last] ^= 0xff;
+    assert!(fixture::verifier()
+        .decode_from_crate_package_ref(&bin)
+        .is_err());
+}
+
+#[test]
+fn test_decode_limits() {
+    use crate::utils::codec::CODECTYPE;
+    use crate::utils::context::DATASECTIONTYPE;
+    use crate::utils::error::CrateSpecError;
+    use crate::utils::fixture;
+    use crate::utils::package::DecodeLimits;
+
+    let mut package_context = fixture::package_context(1000);
+    package_context.set_section_codec(DATASECTIONTYPE::CRATEBIN, CODECTYPE::ZSTD);
+    let (_, _, bin) = package_context.encode_to_crate_package().unwrap();
+
+    let mut limits = DecodeLimits::new();
+    limits.max_crate_size = 1000;
+    let mut package_context_new = PackageContext::new();
+    package_context_new.set_decode_limits(limits.clone());
+    assert!(package_context_new.decode_from_crate_package(&bin).is_ok());
+
+    for limits in [
+        DecodeLimits {
+            max_crate_size: 999,
+            ..limits.clone()
+        },
+        DecodeLimits {
+            max_section_num: 1,
+            ..limits.clone()
+        },
+        DecodeLimits {
+            max_strtable_size: 1,
+            ..limits
+        },
+    ] {
+        let mut package_context_new = PackageContext::new();
+        package_context_new.set_decode_limits(limits);
+        assert!(matches!(
+            package_context_new.decode_from_crate_package(&bin),
+            Err(CrateSpecError::Format(_))
+        ));
+    }
+
+    //corrupt every byte with a valid fingerprint, decoding must fail without panic
+    let body_len = bin.len() - FINGERPRINT_LEN;
+    for i in 0..body_len {
+        let mut tampered_bin = bin[..body_len].to_vec();
+        tampered_bin[i] = 0xff;
+        let fp = PKCS::new().gen_digest_256(&tampered_bin).unwrap();
+        tampered_bin.extend(fp);
+        let _ = PackageContext::new().decode_from_crate_package(&tampered_bin);
+        let _ = PackageContext::new().decode_from_crate_package_ref(&tampered_bin);
+    }
+}
+
+#[test]
+fn test_verification_report() {
+    use crate::utils::context::SIGTYPE;
+    use crate::utils::error::CrateSpecError;
+    use crate::utils::fixture;
+    use crate::utils::verify::VerificationPolicy;
+
+    let mut package_context = fixture::package_context(100);
+    package_context.add_sig(fixture::cert_signer(), SIGTYPE::CRATEBIN);
+    package_context.add_sig(
+        fixture::signer("test/expired-cert.pem", "test/key.pem"),
+        SIGTYPE::MERKLE,
+    );
+    //issued by an intermediate which is not embedded
+    package_context.add_sig(
+        fixture::signer("test/chain-cert.pem", "test/chain-key.pem"),
+        SIGTYPE::MERKLE,
+    );
+    let (_, _, bin) = package_context.encode_to_crate_package().unwrap();
+
+    //every signature has to be valid by default
+    let mut package_context_new = fixture::verifier();
+    assert!(matches!(
+        package_context_new.decode_from_crate_package(bin.as_slice()),
+        Err(CrateSpecError::Trust(_))
+    ));
+    let statuses: Vec<_> = package_context_new
+        .verification_report
+        .sigs
+        .iter()
+        .map(|sig| (sig.index, sig.typ, sig.status))
+        .collect();
+    assert_eq!(
+        vec![
+            (0, 1, SigStatus::Valid),
+            (1, 2, SigStatus::Expired),
+            (2, 2, SigStatus::Untrusted)
+        ],
+        statuses
+    );
+    assert!(package_context_new.verification_report.sigs[0]
+        .reason
+        .is_empty());
+    let signer = package_context_new.sigs[1].signer.as_ref().unwrap();
+    assert!(signer.subject.ends_with("CN=expired.foobar.com"));
+
+    let mut package_context_new = fixture::verifier();
+    package_context_new.set_verification_policy(VerificationPolicy::at_least(1).require_type(1));
+    package_context_new
+        .decode_from_crate_package_ref(bin.as_slice())
+        .unwrap();
+    assert_eq!(1, package_context_new.verification_report.valid_num());
+
+    let mut package_context_new = fixture::verifier();
+    package_context_new.set_verification_policy(VerificationPolicy::at_least(1).require_type(2));
+    assert!(matches!(
+        package_context_new.decode_from_crate_package(bin.as_slice()),
+        Err(CrateSpecError::Signature(_))
+    ));
+}
+
+#[test]
+fn test_raw_sig_scheme() {
+    use crate::utils::context::SIGTYPE;
+    use crate::utils::error::CrateSpecError;
+    use crate::utils::fixture;
+    use crate::utils::pkcs::SIGSCHEME;
+
+    let mut package_context = fixture::package_context(100);
+    let mut ed25519 = PKCS::new();
+    ed25519
+        .load_raw_key_from_file("test/ed25519-key.pem".to_string(), SIGSCHEME::ED25519)
+        .unwrap();
+    package_context.add_sig(ed25519, SIGTYPE::CRATEBIN);
+    let mut p256 = PKCS::new();
+    p256.load_raw_key_from_file("test/p256-key.pem".to_string(), SIGSCHEME::P256)
+        .unwrap();
+    package_context.add_sig(p256, SIGTYPE::MERKLE);
+    let (_, _, bin) = package_context.encode_to_crate_package().unwrap();
+
+    let mut package_context_new = PackageContext::new();
+    package_context_new.set_keyring_bin(
+        PKCS::keyring_bins(
+            [
+                "test/ed25519-pub.pem".to_string(),
+                "test/p256-pub.pem".to_string(),
+            ]
+            .to_vec(),
+        )
+        .unwrap(),
+    );
+    package_context_new
+        .decode_from_crate_package_ref(bin.as_slice())
+        .unwrap();
+    assert_eq!(2, package_context_new.verification_report.valid_num());
+    assert_eq!(
+        vec![SIG_SCHEME_ED25519, SIG_SCHEME_P256],
+        package_context_new
+            .sigs
+            .iter()
+            .map(|sig| sig.scheme)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        64,
+        package_context_new.sigs[0]
+            .signer
+            .as_ref()
+            .unwrap()
+            .fingerprint
+            .len()
+    );
+
+    //raw signatures are not trusted by root-ca
+    let mut package_context_new = PackageContext::new();
+    package_context_new
+        .set_root_cas_bin(PKCS::root_ca_bins(["test/root-ca.pem".to_string()].to_vec()).unwrap());
+    assert!(matches!(
+        package_context_new.decode_from_crate_package(bin.as_slice()),
+        Err(CrateSpecError::Trust(_))
+    ));
+
+    //sig scheme is not written before version 9
+    package_context.set_crate_version(8).unwrap();
+    assert!(matches!(
+        package_context.encode_to_crate_package(),
+        Err(CrateSpecError::Signature(_))
+    ));
+}
+
+#[test]
+fn test_cert_sig_scheme() {
+    use crate::utils::context::SIGTYPE;
+    use crate::utils::fixture;
+    use crate::utils::pkcs::SIGSCHEME;
+
+    //the chosen certificate scheme is the one recorded, cms falls back to pkcs7 before version 9
+    for (scheme, c_version, written) in [
+        (SIGSCHEME::CMS, 9, SIG_SCHEME_CMS),
+        (SIGSCHEME::PKCS7, 9, SIG_SCHEME_PKCS7),
+        (SIGSCHEME::CMS, 8, SIG_SCHEME_PKCS7),
+    ] {
+        let mut pkcs = fixture::cert_signer();
+        pkcs.set_cert_scheme(scheme).unwrap();
+        let mut package_context = fixture::package_context(100);
+        package_context.add_sig(pkcs, SIGTYPE::CRATEBIN);
+        package_context.set_crate_version(c_version).unwrap();
+        let package_context_new = fixture::round_trip(&mut package_context);
+        assert_eq!(written, package_context_new.sigs[0].scheme);
+    }
+}
@@ -1,42 +1,79 @@
-use crate::utils::context::{PackageContext, StringTable, NOT_SIG_NUM};
+use crate::utils::codec::{encode_by_codec, CODEC_NONE};
+use crate::utils::context::{
+    insert_strs, DepKind, PackageContext, StringTable, DATASECTIONTYPE, NOT_SIG_NUM,
+};
+use crate::utils::error::CrateSpecError;
 use crate::utils::package::{
-    datasection_type, CrateBinarySection, CratePackage, DataSection, DataSectionCollectionType,
-    DepTableEntry, DepTableSection, LenArrayType, Off, PackageSection, RawArrayType,
-    SectionIndexEntry, SigStructureSection, Size, CRATEVERSION, FINGERPRINT_LEN, MAGIC_NUMBER,
+    align_up, datasection_codec, datasection_critical, datasection_type, is_supported_version,
+    pad_len, CompressedSection, CrateBinarySection, CratePackage, DataSection,
+    DataSectionCollectionType, DepTableEntry, DepTableSection, FeatureTableEntry,
+    FeatureTableSection, LenArrayType, LockTableEntry, LockTableSection, Off, PackageSection,
+    ProvenanceSection, RawArrayType, SectionIndexEntry, SigStructureSection, Size, Type, Uchar,
+    UnknownSection, FINGERPRINT_LEN, MAGIC_NUMBER, NO_ALIGN, SECTION_CRITICAL_FLAG,
 };
 
-use crate::utils::package::gen_bincode::{encode2vec_by_bincode, encode_size_by_bincode};
-use crate::utils::pkcs::PKCS;
+use crate::utils::package::gen_bincode::{
+    encode2vec_by_bincode, encode_size_by_bincode, encode_to_hash_writer, HashWriter,
+};
+use crate::utils::pkcs::{PKCS, SIG_SCHEME_PKCS7};
+use bincode::enc::write::Writer;
+use std::io::{self, Seek, SeekFrom, Write};
 
 impl CratePackage {
-    pub fn set_section_index(&mut self) {
+    pub fn set_section_index(&mut self) -> Result<(), CrateSpecError> {
         self.section_index.entries.arr = vec![];
         for (i, (_size, _off)) in self.data_sections.encode_size_offset().iter().enumerate() {
             let size = *_size;
             let off = *_off;
-            let typ = datasection_type(&self.data_sections.col.arr[i]);
-            self.section_index.entries.arr.push(SectionIndexEntry::new(
-                typ,
-                off as Off,
-                size as Size,
-            ));
+            let section = &self.data_sections.col.arr[i];
+            let mut typ = datasection_type(section);
+            if datasection_critical(section) {
+                typ |= SECTION_CRITICAL_FLAG;
+            }
+            let mut entry = SectionIndexEntry::new(typ, off as Off, size as Size);
+            entry.sh_codec = datasection_codec(section);
+            if self.crate_header.c_version >= 2 {
+                let digest = PKCS::new().gen_digest_256(&encode2vec_by_bincode(section))?;
+                entry.sh_digest.copy_from_slice(digest.as_slice());
+            }
+            self.section_index.entries.arr.push(entry);
         }
+        Ok(())
+    }
+
+    ///must be called after string table and section index are set
+    pub fn set_merkle_root(&mut self) -> Result<(), CrateSpecError> {
+        if self.crate_header.c_version >= 2 {
+            self.crate_header.merkle_root = self.calc_merkle_root()?;
+        }
+        Ok(())
     }
 
     pub fn set_string_table(&mut self, str_table: &StringTable) {
         self.string_table = RawArrayType::from_vec(str_table.to_bytes());
     }
 
+    pub fn set_crate_version(&mut self, c_version: Uchar) {
+        self.crate_header.c_version = c_version;
+        self.section_index.c_version = c_version;
+    }
+
+    pub fn set_align(&mut self, align: Uchar) {
+        self.crate_header.c_align = align;
+        self.section_index.align = align;
+        self.data_sections.align = align;
+    }
+
     pub fn set_crate_header(&mut self, fake_num: usize) {
-        self.crate_header.c_version = CRATEVERSION;
         self.crate_header.strtable_size = self.string_table.size() as Size;
         self.crate_header.strtable_offset =
             (self.crate_header.size() + self.magic_number.len()) as Size;
         self.crate_header.si_size = self.section_index.size() as Size
-            + (fake_num * SectionIndexEntry::default().size()) as Size;
+            + (fake_num * self.section_index.entry_size()) as Size;
         self.crate_header.si_num = self.section_index.num() as Size + fake_num as Size;
-        self.crate_header.si_offset =
-            self.crate_header.strtable_offset + self.crate_header.strtable_size;
+        self.crate_header.si_offset = (self.crate_header.strtable_offset
+            + self.crate_header.strtable_size)
+            + self.strtable_pad_len() as Size;
         self.crate_header.ds_offset = self.crate_header.si_offset + self.crate_header.si_size;
     }
 
@@ -47,47 +84,299 @@ impl CratePackage {
     pub fn set_finger_print(&mut self, fp: Vec<u8>) {
         self.finger_print.copy_from_slice(fp.as_slice());
     }
+
+    ///magic number, crate header, string table and section index
+    pub fn encode_head_to_vec(&self) -> Vec<u8> {
+        let mut buf = encode2vec_by_bincode(&self.magic_number);
+        buf.extend(encode2vec_by_bincode(&self.crate_header));
+        buf.extend(encode2vec_by_bincode(&self.string_table));
+        buf.extend(vec![0; self.strtable_pad_len()]);
+        buf.extend(encode2vec_by_bincode(&self.section_index));
+        buf
+    }
+}
+
+///none-sig section, crate binary is borrowed rather than copied
+enum StreamSection<'a> {
+    Section(DataSection),
+    CrateBinary(&'a [u8]),
+}
+
+impl StreamSection<'_> {
+    ///crate binary is copied into its section
+    fn into_data_section(self) -> DataSection {
+        match self {
+            StreamSection::Section(section) => section,
+            StreamSection::CrateBinary(bytes) => {
+                let mut binary_section = CrateBinarySection::new();
+                binary_section.bin = RawArrayType::from_vec(bytes.to_vec());
+                DataSection::CrateBinarySection(binary_section)
+            }
+        }
+    }
+
+    fn typ(&self) -> Type {
+        match self {
+            StreamSection::Section(section) => {
+                let mut typ = datasection_type(section);
+                if datasection_critical(section) {
+                    typ |= SECTION_CRITICAL_FLAG;
+                }
+                typ
+            }
+            StreamSection::CrateBinary(_) => DATASECTIONTYPE::CRATEBIN as Type,
+        }
+    }
+
+    fn codec(&self) -> Uchar {
+        match self {
+            StreamSection::Section(section) => datasection_codec(section),
+            StreamSection::CrateBinary(_) => CODEC_NONE,
+        }
+    }
+
+    fn encode_to<W: Write>(&self, writer: &mut HashWriter<W>) -> Result<(), CrateSpecError> {
+        match self {
+            StreamSection::Section(section) => encode_to_hash_writer(section, writer)?,
+            StreamSection::CrateBinary(bytes) => writer.write(bytes)?,
+        }
+        Ok(())
+    }
+
+    ///write section and give back its index entry, offset must be aligned
+    fn write_to<W: Write>(
+        &self,
+        writer: &mut W,
+        offset: usize,
+        c_version: Uchar,
+    ) -> Result<SectionIndexEntry, CrateSpecError> {
+        let mut hash_writer = HashWriter::new(Some(writer));
+        self.encode_to(&mut hash_writer)?;
+        let mut entry =
+            SectionIndexEntry::new(self.typ(), offset as Off, hash_writer.bytes_written as Size);
+        entry.sh_codec = self.codec();
+        if c_version >= 2 {
+            entry.sh_digest = hash_writer.finish();
+        }
+        Ok(entry)
+    }
 }
 
 impl PackageContext {
+    ///push section, its payload is encoded by the codec set for its type
+    fn push_data_section(
+        &self,
+        sections: &mut Vec<StreamSection>,
+        section: DataSection,
+    ) -> Result<(), CrateSpecError> {
+        let mut typ = datasection_type(&section);
+        let codec = self.section_codec(typ);
+        if codec == CODEC_NONE {
+            sections.push(StreamSection::Section(section));
+            return Ok(());
+        }
+        if datasection_critical(&section) {
+            typ |= SECTION_CRITICAL_FLAG;
+        }
+        sections.push(StreamSection::Section(self.compressed_section(
+            typ,
+            codec,
+            &encode2vec_by_bincode(&section),
+        )?));
+        Ok(())
+    }
+
+    ///section align needs version 3
+    fn check_align(&self) -> Result<(), CrateSpecError> {
+        if self.section_align != NO_ALIGN && self.crate_version < 3 {
+            return Err(CrateSpecError::Format(format!(
+                "section align needs version 3, version {} is written",
+                self.crate_version
+            )));
+        }
+        Ok(())
+    }
+
+    ///package metadata the written package section can not hold is refused rather than dropped
+    fn check_package(&self) -> Result<(), CrateSpecError> {
+        if self.crate_version < 4 && self.pack_info.has_extended_metadata() {
+            return Err(CrateSpecError::Format(format!(
+                "extended package metadata needs version 4, version {} is written",
+                self.crate_version
+            )));
+        }
+        Ok(())
+    }
+
+    ///dependencies the written dependency table can not hold are refused rather than dropped
+    fn check_deps(&self) -> Result<(), CrateSpecError> {
+        for dep_info in self.dep_infos.iter() {
+            if dep_info.kind != DepKind::Normal && self.crate_version < 5 {
+                return Err(CrateSpecError::Format(format!(
+                    "dev/build dependency {} needs version 5, version {} is written",
+                    dep_info.name, self.crate_version
+                )));
+            }
+            let need_version = if dep_info.src.is_extended() {
+                7
+            } else if dep_info.has_attributes() {
+                6
+            } else {
+                continue;
+            };
+            if self.crate_version < need_version {
+                return Err(CrateSpecError::Format(format!(
+                    "dependency {} needs version {}, version {} is written",
+                    dep_info.name, need_version, self.crate_version
+                )));
+            }
+        }
+        if self.crate_version < 8 && !self.unrepresented_deps.is_empty() {
+            return Err(CrateSpecError::Format(format!(
+                "unrepresented dependencies need version 8, version {} is written",
+                self.crate_version
+            )));
+        }
+        Ok(())
+    }
+
+    ///scheme of each sig at the version written, raw schemes need version 9
+    fn set_sig_schemes(&mut self) -> Result<(), CrateSpecError> {
+        for siginfo in self.sigs.iter_mut() {
+            siginfo.scheme = siginfo.pkcs.scheme_by_version(self.crate_version);
+            if siginfo.scheme != SIG_SCHEME_PKCS7 && self.crate_version < 9 {
+                return Err(CrateSpecError::Signature(format!(
+                    "sig scheme {} needs version 9, version {} is written",
+                    siginfo.scheme, self.crate_version
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    ///payload is the encoded section
+    fn compressed_section(
+        &self,
+        typ: Type,
+        codec: Uchar,
+        payload: &[u8],
+    ) -> Result<DataSection, CrateSpecError> {
+        if self.crate_version < 1 {
+            return Err(CrateSpecError::Format(format!(
+                "section codec needs version 1, version {} is written",
+                self.crate_version
+            )));
+        }
+        let mut compressed_section = CompressedSection::new(typ, codec);
+        compressed_section.bytes = RawArrayType::from_vec(encode_by_codec(codec, payload)?);
+        Ok(DataSection::CompressedSection(compressed_section))
+    }
+
     fn write_to_data_section_collection_without_sig(
         &self,
         dsc: &mut DataSectionCollectionType,
         str_table: &mut StringTable,
-    ) {
-        let mut package_section = PackageSection::new();
-        self.write_to_package_section(&mut package_section, str_table);
+    ) -> Result<(), CrateSpecError> {
+        let sections = self.sections_without_sig(str_table, &self.crate_binary.bytes)?;
         dsc.col
             .arr
-            .push(DataSection::PackageSection(package_section));
+            .extend(sections.into_iter().map(StreamSection::into_data_section));
+        Ok(())
+    }
+
+    ///none-sig sections in file order, shared by encode_to_crate_package and encode_to_writer
+    fn sections_without_sig<'a>(
+        &self,
+        str_table: &mut StringTable,
+        crate_bytes: &'a [u8],
+    ) -> Result<Vec<StreamSection<'a>>, CrateSpecError> {
+        let mut sections = vec![];
+        let mut package_section = PackageSection::new();
+        self.write_to_package_section(&mut package_section, str_table);
+        self.push_data_section(&mut sections, DataSection::PackageSection(package_section))?;
 
         let mut dep_table_section = DepTableSection::new();
         self.write_to_dep_table_section(&mut dep_table_section, str_table);
-        dsc.col
-            .arr
-            .push(DataSection::DepTableSection(dep_table_section));
+        self.push_data_section(
+            &mut sections,
+            DataSection::DepTableSection(dep_table_section),
+        )?;
 
-        let mut binary_section = CrateBinarySection::new();
-        self.write_to_crate_binary_section(&mut binary_section);
-        dsc.col
-            .arr
-            .push(DataSection::CrateBinarySection(binary_section));
+        if !self.feature_infos.is_empty() {
+            let mut feature_table_section = FeatureTableSection::new();
+            self.write_to_feature_table_section(&mut feature_table_section, str_table);
+            self.push_data_section(
+                &mut sections,
+                DataSection::FeatureTableSection(feature_table_section),
+            )?;
+        }
+
+        if !self.lock_infos.is_empty() {
+            let mut lock_table_section = LockTableSection::new();
+            self.write_to_lock_table_section(&mut lock_table_section, str_table);
+            self.push_data_section(
+                &mut sections,
+                DataSection::LockTableSection(lock_table_section),
+            )?;
+        }
+
+        if let Some(provenance) = &self.provenance {
+            let mut provenance_section = ProvenanceSection::new();
+            provenance.write_to_provenance_section(&mut provenance_section, str_table);
+            self.push_data_section(
+                &mut sections,
+                DataSection::ProvenanceSection(provenance_section),
+            )?;
+        }
+
+        let typ = DATASECTIONTYPE::CRATEBIN as Type;
+        let codec = self.section_codec(typ);
+        if codec == CODEC_NONE {
+            sections.push(StreamSection::CrateBinary(crate_bytes));
+        } else {
+            //crate binary section is encoded as its raw bytes
+            sections.push(StreamSection::Section(self.compressed_section(
+                typ,
+                codec,
+                crate_bytes,
+            )?));
+        }
+
+        for unknown_info in self.unknown_sections.iter() {
+            let mut unknown_section = UnknownSection::new(unknown_info.typ, unknown_info.codec);
+            unknown_info.write_to_unknown_section(&mut unknown_section);
+            sections.push(StreamSection::Section(DataSection::UnknownSection(
+                unknown_section,
+            )));
+        }
+        Ok(sections)
+    }
+
+    fn none_sig_num(&self) -> usize {
+        NOT_SIG_NUM
+            + !self.feature_infos.is_empty() as usize
+            + !self.lock_infos.is_empty() as usize
+            + self.provenance.is_some() as usize
+            + self.unknown_sections.len()
     }
 
     pub fn write_to_data_section_collection_sig(&self, dsc: &mut DataSectionCollectionType) {
         for siginfo in self.sigs.iter() {
             let mut sig = SigStructureSection::new();
+            sig.c_version = self.crate_version;
             siginfo.write_to_sig_structure_section(&mut sig);
             dsc.col.arr.push(DataSection::SigStructureSection(sig));
         }
     }
 
     fn write_to_package_section(&self, ps: &mut PackageSection, str_table: &mut StringTable) {
+        ps.c_version = self.crate_version;
         self.pack_info.write_to_package_section(ps, str_table);
         encode_size_by_bincode(ps);
     }
 
     fn write_to_dep_table_section(&self, dts: &mut DepTableSection, str_table: &mut StringTable) {
+        dts.c_version = self.crate_version;
         let mut entries = vec![];
         self.dep_infos.iter().for_each(|dep_info| {
             let mut dte = DepTableEntry::new();
@@ -95,46 +384,93 @@ impl PackageContext {
             entries.push(dte);
         });
         dts.entries = LenArrayType::from_vec(entries);
+        if dts.c_version >= 8 {
+            dts.dep_unrepresented = insert_strs(&self.unrepresented_deps, str_table);
+        }
     }
 
-    fn write_to_crate_binary_section(&self, cbs: &mut CrateBinarySection) {
-        self.crate_binary.write_to_crate_binary_section(cbs);
+    fn write_to_feature_table_section(
+        &self,
+        fts: &mut FeatureTableSection,
+        str_table: &mut StringTable,
+    ) {
+        let mut entries = vec![];
+        self.feature_infos.iter().for_each(|feature_info| {
+            let mut fte = FeatureTableEntry::new();
+            feature_info.write_to_feature_table_entry(&mut fte, str_table);
+            entries.push(fte);
+        });
+        fts.entries = LenArrayType::from_vec(entries);
+    }
+
+    fn write_to_lock_table_section(&self, lts: &mut LockTableSection, str_table: &mut StringTable) {
+        let mut entries = vec![];
+        self.lock_infos.iter().for_each(|lock_info| {
+            let mut lte = LockTableEntry::new();
+            lock_info.write_to_lock_table_entry(&mut lte, str_table);
+            entries.push(lte);
+        });
+        lts.entries = LenArrayType::from_vec(entries);
     }
+
     fn set_sigs(&self, crate_package: &mut CratePackage, non_sig_num: usize) {
         crate_package.data_sections.col.arr.truncate(non_sig_num);
         self.write_to_data_section_collection_sig(&mut crate_package.data_sections);
     }
 
-    fn set_pack_dep_bin(&self, crate_package: &mut CratePackage, str_table: &mut StringTable) {
+    fn set_pack_dep_bin(
+        &self,
+        crate_package: &mut CratePackage,
+        str_table: &mut StringTable,
+    ) -> Result<(), CrateSpecError> {
         self.write_to_data_section_collection_without_sig(
             &mut crate_package.data_sections,
             str_table,
-        );
+        )
     }
 
-    fn calc_sigs(&mut self, crate_package: &CratePackage) {
+    fn calc_sigs(&mut self, crate_package: &CratePackage) -> Result<(), CrateSpecError> {
         let bin_all = encode2vec_by_bincode(crate_package);
-        let bin_all = self.binary_before_sig(crate_package, bin_all.as_slice());
-        let bin_crate = crate_package.crate_binary_section().bin.arr.as_slice();
-        self.sigs.iter_mut().for_each(|siginfo| {
-            let digest;
-            match siginfo.typ {
-                0 => {
-                    digest = siginfo.pkcs.gen_digest_256(bin_all.as_slice());
+        let bin_all = self.binary_before_sig(crate_package, bin_all.as_slice())?;
+        let file_digest = PKCS::new().gen_digest_256(bin_all.as_slice())?;
+        //CRATEBIN sig is defined over crate bytes before codec
+        let crate_digest = PKCS::new().gen_digest_256(self.crate_binary.bytes.as_slice())?;
+        self.sign_digests(crate_package, &file_digest, &crate_digest)
+    }
+
+    fn sign_digests(
+        &mut self,
+        crate_package: &CratePackage,
+        file_digest: &[u8],
+        crate_digest: &[u8],
+    ) -> Result<(), CrateSpecError> {
+        for siginfo in self.sigs.iter_mut() {
+            let digest = match siginfo.typ {
+                0 => file_digest,
+                1 => crate_digest,
+                //merkle root is a digest already
+                2 if crate_package.crate_header.c_version >= 2 => {
+                    crate_package.crate_header.merkle_root.as_slice()
                 }
-                1 => {
-                    digest = siginfo.pkcs.gen_digest_256(bin_crate);
+                2 => {
+                    return Err(CrateSpecError::Signature(format!(
+                        "merkle sig needs version 2, version {} is written",
+                        crate_package.crate_header.c_version
+                    )));
                 }
                 _ => {
-                    panic!("sig type is not right!")
+                    return Err(CrateSpecError::Signature(
+                        "sig type is not right!".to_string(),
+                    ));
                 }
-            }
-            siginfo.bin = siginfo.pkcs.encode_pkcs_bin(digest.as_slice());
+            };
+            siginfo.bin = siginfo.pkcs.sign_digest(digest, siginfo.scheme)?;
             siginfo.size = siginfo.bin.len();
-        });
+        }
+        Ok(())
     }
 
-    fn calc_fingerprint(&self, crate_package: &CratePackage) -> Vec<u8> {
+    fn calc_fingerprint(&self, crate_package: &CratePackage) -> Result<Vec<u8>, CrateSpecError> {
         let bin_all = encode2vec_by_bincode(crate_package);
         PKCS::new().gen_digest_256(&bin_all[..bin_all.len() - FINGERPRINT_LEN])
     }
@@ -144,39 +480,279 @@ impl PackageContext {
         &self,
         str_table: &mut StringTable,
         crate_package: &mut CratePackage,
-    ) {
+    ) -> Result<(), CrateSpecError> {
         crate_package.set_magic_numer();
-        self.set_pack_dep_bin(crate_package, str_table);
+        self.set_pack_dep_bin(crate_package, str_table)?;
         //this is setting fake sigsection
-        self.set_sigs(crate_package, NOT_SIG_NUM);
-        crate_package.set_section_index();
+        self.set_sigs(crate_package, self.none_sig_num());
+        crate_package.set_section_index()?;
         crate_package.set_string_table(str_table);
+        crate_package.set_merkle_root()?;
         crate_package.set_crate_header(0);
+        Ok(())
     }
 
     //2 sig
-    fn encode_sig_to_crate_package(&mut self, crate_package: &mut CratePackage) {
-        self.calc_sigs(crate_package);
+    fn encode_sig_to_crate_package(
+        &mut self,
+        crate_package: &mut CratePackage,
+    ) -> Result<(), CrateSpecError> {
+        self.calc_sigs(crate_package)?;
         //this is setting true sigsection
-        self.set_sigs(crate_package, NOT_SIG_NUM);
+        self.set_sigs(crate_package, self.none_sig_num());
+        Ok(())
     }
 
     //3 after sig
-    fn encode_to_crate_package_after_sig(&self, crate_package: &mut CratePackage) {
-        crate_package.set_section_index();
+    fn encode_to_crate_package_after_sig(
+        &self,
+        crate_package: &mut CratePackage,
+    ) -> Result<(), CrateSpecError> {
+        crate_package.set_section_index()?;
         crate_package.set_crate_header(0);
-        let finger_print = self.calc_fingerprint(crate_package);
+        let finger_print = self.calc_fingerprint(crate_package)?;
         crate_package.set_finger_print(finger_print);
+        Ok(())
     }
 
     //1 2 3
-    pub fn encode_to_crate_package(&mut self) -> (CratePackage, StringTable, Vec<u8>) {
+    pub fn encode_to_crate_package(
+        &mut self,
+    ) -> Result<(CratePackage, StringTable, Vec<u8>), CrateSpecError> {
+        if !is_supported_version(self.crate_version) {
+            return Err(CrateSpecError::UnsupportedVersion(self.crate_version));
+        }
+        self.check_align()?;
+        self.check_package()?;
+        self.check_deps()?;
+        self.set_sig_schemes()?;
         let mut crate_package = CratePackage::new();
+        crate_package.set_crate_version(self.crate_version);
+        crate_package.set_align(self.section_align);
         let mut str_table = StringTable::new();
-        self.encode_to_crate_package_before_sig(&mut str_table, &mut crate_package);
-        self.encode_sig_to_crate_package(&mut crate_package);
-        self.encode_to_crate_package_after_sig(&mut crate_package);
+        self.encode_to_crate_package_before_sig(&mut str_table, &mut crate_package)?;
+        self.encode_sig_to_crate_package(&mut crate_package)?;
+        self.encode_to_crate_package_after_sig(&mut crate_package)?;
         let bin = encode2vec_by_bincode(&crate_package);
-        (crate_package, str_table, bin)
+        Ok((crate_package, str_table, bin))
+    }
+
+    ///same bytes as encode_to_crate_package, but sections are streamed to writer once
+    ///and header and section index are written back at the end
+    pub fn encode_to_writer<W: Write + Seek>(
+        &mut self,
+        writer: &mut W,
+    ) -> Result<(), CrateSpecError> {
+        let crate_bytes = std::mem::take(&mut self.crate_binary.bytes);
+        let res = self.encode_stream(writer, crate_bytes.as_slice());
+        self.crate_binary.bytes = crate_bytes;
+        res
+    }
+
+    fn encode_stream<W: Write + Seek>(
+        &mut self,
+        writer: &mut W,
+        crate_bytes: &[u8],
+    ) -> Result<(), CrateSpecError> {
+        if !is_supported_version(self.crate_version) {
+            return Err(CrateSpecError::UnsupportedVersion(self.crate_version));
+        }
+        self.check_align()?;
+        self.check_package()?;
+        self.check_deps()?;
+        self.set_sig_schemes()?;
+        let c_version = self.crate_version;
+        let align = self.section_align;
+        let start = writer.stream_position()?;
+        let mut str_table = StringTable::new();
+        let sections = self.sections_without_sig(&mut str_table, crate_bytes)?;
+        let mut crate_package = CratePackage::new();
+        crate_package.set_crate_version(c_version);
+        crate_package.set_align(align);
+        crate_package.set_string_table(&str_table);
+        crate_package.set_crate_header(sections.len() + self.sigs.len());
+
+        //1 none-sig sections after room for head
+        let ds_offset = crate_package.crate_header.ds_offset as usize;
+        writer.write_all(&vec![0; ds_offset])?;
+        let mut offset = 0;
+        for section in sections.iter() {
+            writer.write_all(&vec![0; pad_len(offset, align)])?;
+            offset = align_up(offset, align);
+            let entry = section.write_to(writer, offset, c_version)?;
+            offset += entry.sh_size as usize;
+            crate_package.section_index.entries.arr.push(entry);
+        }
+        crate_package.set_merkle_root()?;
+        crate_package.set_crate_header(self.sigs.len());
+
+        //2 sig, sig entries are zero in signed bytes
+        let mut file_writer = HashWriter::<io::Sink>::new(None);
+        file_writer.write(&crate_package.encode_head_to_vec())?;
+        file_writer.write(&vec![
+            0;
+            self.sigs.len()
+                * crate_package.section_index.entry_size()
+        ])?;
+        //ds_offset is aligned, so padding follows from bytes written
+        for section in sections.iter() {
+            file_writer.write(&vec![0; pad_len(file_writer.bytes_written, align)])?;
+            section.encode_to(&mut file_writer)?;
+        }
+        let file_digest = file_writer.finish();
+        let crate_digest = PKCS::new().gen_digest_256(crate_bytes)?;
+        self.sign_digests(&crate_package, &file_digest, &crate_digest)?;
+
+        let mut sig_dsc = DataSectionCollectionType::new();
+        self.write_to_data_section_collection_sig(&mut sig_dsc);
+        let sig_sections: Vec<StreamSection> = sig_dsc
+            .col
+            .arr
+            .into_iter()
+            .map(StreamSection::Section)
+            .collect();
+        for section in sig_sections.iter() {
+            writer.write_all(&vec![0; pad_len(offset, align)])?;
+            offset = align_up(offset, align);
+            let entry = section.write_to(writer, offset, c_version)?;
+            offset += entry.sh_size as usize;
+            crate_package.section_index.entries.arr.push(entry);
+        }
+        crate_package.set_crate_header(0);
+
+        //3 write head back, fingerprint goes last
+        let head = crate_package.encode_head_to_vec();
+        writer.seek(SeekFrom::Start(start))?;
+        writer.write_all(&head)?;
+        let mut fp_writer = HashWriter::<io::Sink>::new(None);
+        fp_writer.write(&head)?;
+        for section in sections.iter().chain(sig_sections.iter()) {
+            fp_writer.write(&vec![0; pad_len(fp_writer.bytes_written, align)])?;
+            section.encode_to(&mut fp_writer)?;
+        }
+        writer.seek(SeekFrom::Start(start + (ds_offset + offset) as u64))?;
+        writer.write_all(&fp_writer.finish())?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_encode_to_writer() {
+    use crate::utils::codec::CODECTYPE;
+    use crate::utils::context::{UnknownSectionInfo, SIGTYPE};
+    use crate::utils::fixture;
+    use std::io::Cursor;
+
+    fn unsigned_context() -> PackageContext {
+        let mut package_context = fixture::package_context(1000);
+        package_context
+            .unknown_sections
+            .push(UnknownSectionInfo::new(100, [1, 2, 3].to_vec()));
+        package_context
+    }
+
+    //signatures carry signing time, so compare unsigned files
+    let mut package_context = unsigned_context();
+    let (_, _, bin) = package_context.encode_to_crate_package().unwrap();
+    let mut cursor = Cursor::new(vec![]);
+    package_context.encode_to_writer(&mut cursor).unwrap();
+    assert_eq!(bin, cursor.into_inner());
+    assert_eq!([15; 1000].to_vec(), package_context.crate_binary.bytes);
+
+    let mut package_context = unsigned_context();
+    package_context.set_section_codec(DATASECTIONTYPE::CRATEBIN, CODECTYPE::ZSTD);
+    let (_, _, bin) = package_context.encode_to_crate_package().unwrap();
+    let mut cursor = Cursor::new(vec![]);
+    package_context.encode_to_writer(&mut cursor).unwrap();
+    assert_eq!(bin, cursor.into_inner());
+
+    let mut package_context = unsigned_context();
+    for sig_type in [SIGTYPE::FILE, SIGTYPE::CRATEBIN, SIGTYPE::MERKLE] {
+        package_context.add_sig(fixture::cert_signer(), sig_type);
+    }
+    let mut cursor = Cursor::new(vec![]);
+    package_context.encode_to_writer(&mut cursor).unwrap();
+    let mut package_context_new = fixture::verifier();
+    package_context_new
+        .decode_from_crate_package(cursor.get_ref())
+        .unwrap();
+    assert_eq!([15; 1000].to_vec(), package_context_new.crate_binary.bytes);
+}
+
+#[test]
+fn test_section_align() {
+    use crate::utils::context::{UnknownSectionInfo, SIGTYPE};
+    use crate::utils::error::CrateSpecError;
+    use crate::utils::fixture;
+    use std::io::Cursor;
+
+    fn package_context(align: Uchar) -> PackageContext {
+        let mut package_context = fixture::package_context(999);
+        package_context
+            .unknown_sections
+            .push(UnknownSectionInfo::new(100, [1, 2, 3].to_vec()));
+        package_context.set_section_align(align).unwrap();
+        package_context
+    }
+
+    assert!(PackageContext::new().set_section_align(3).is_err());
+    let mut package_context_v2 = package_context(4);
+    package_context_v2.set_crate_version(2).unwrap();
+    assert!(matches!(
+        package_context_v2.encode_to_crate_package(),
+        Err(CrateSpecError::Format(_))
+    ));
+
+    for align in [4, 8] {
+        let (crate_package, _, bin) = package_context(align).encode_to_crate_package().unwrap();
+        let mut cursor = Cursor::new(vec![]);
+        package_context(align)
+            .encode_to_writer(&mut cursor)
+            .unwrap();
+        assert_eq!(bin, cursor.into_inner());
+
+        let header = &crate_package.crate_header;
+        assert_eq!(align, header.c_align);
+        assert_eq!(0, pad_len(header.si_offset as usize, align));
+        assert_eq!(0, pad_len(header.ds_offset as usize, align));
+        let entries = &crate_package.section_index.entries.arr;
+        for entry in entries.iter() {
+            assert_eq!(0, pad_len(entry.sh_offset as usize, align));
+        }
+
+        let mut package_context_new = PackageContext::new();
+        package_context_new.decode_from_crate_package(&bin).unwrap();
+        assert_eq!(align, package_context_new.section_align);
+        assert_eq!([15; 999].to_vec(), package_context_new.crate_binary.bytes);
+
+        //padding after crate binary must stay zero
+        let gap = header.ds_offset as usize + (entries[2].sh_offset + entries[2].sh_size) as usize;
+        assert!(gap < header.ds_offset as usize + entries[3].sh_offset as usize);
+        let mut tampered_bin = bin[..bin.len() - FINGERPRINT_LEN].to_vec();
+        tampered_bin[gap] = 1;
+        let fp = PKCS::new().gen_digest_256(&tampered_bin).unwrap();
+        tampered_bin.extend(fp);
+        assert!(PackageContext::new()
+            .decode_from_crate_package(&tampered_bin)
+            .is_err());
+    }
+
+    let mut package_context = package_context(8);
+    for sig_type in [SIGTYPE::FILE, SIGTYPE::CRATEBIN, SIGTYPE::MERKLE] {
+        package_context.add_sig(fixture::cert_signer(), sig_type);
+    }
+    let (_, _, bin) = package_context.encode_to_crate_package().unwrap();
+    let mut cursor = Cursor::new(vec![]);
+    package_context.encode_to_writer(&mut cursor).unwrap();
+    for bin in [bin, cursor.into_inner()] {
+        fixture::verifier().decode_from_crate_package(&bin).unwrap();
+        let mut package_context_new = fixture::verifier();
+        let crate_package = package_context_new
+            .decode_from_crate_package_ref(&bin)
+            .unwrap();
+        assert_eq!(
+            [15; 999].as_slice(),
+            crate_package.crate_binary().unwrap().as_ref()
+        );
     }
 }
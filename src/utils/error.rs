@@ -0,0 +1,83 @@
+//!error definition
+use bincode::error::{DecodeError, EncodeError};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io;
+
+///errors raised while encoding, decoding, signing or verifying a crate package
+#[derive(Debug)]
+pub enum CrateSpecError {
+    ///file layout is not right
+    Format(String),
+    ///file format version can not be read or written by this crate
+    UnsupportedVersion(u8),
+    ///whole-file fingerprint does not match
+    Fingerprint,
+    ///part of the file does not match its digest, names the part
+    Corrupted(String),
+    ///signature can not be produced, parsed or does not match
+    Signature(String),
+    ///signer certificate is not trusted by root-ca
+    Trust(String),
+    ///file system error
+    Io(io::Error),
+    ///Cargo.toml can not be parsed or misses required keys
+    Toml(String),
+}
+
+impl Display for CrateSpecError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CrateSpecError::Format(s) => write!(f, "{}", s),
+            CrateSpecError::UnsupportedVersion(v) => write!(f, "unsupported version {}", v),
+            CrateSpecError::Fingerprint => write!(f, "fingerprint not right"),
+            CrateSpecError::Corrupted(s) => write!(f, "{} corrupted", s),
+            CrateSpecError::Signature(s) => write!(f, "{}", s),
+            CrateSpecError::Trust(s) => write!(f, "certificate not trusted - {}", s),
+            CrateSpecError::Io(e) => write!(f, "io error - {}", e),
+            CrateSpecError::Toml(s) => write!(f, "toml not right - {}", s),
+        }
+    }
+}
+
+impl Error for CrateSpecError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CrateSpecError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CrateSpecError {
+    fn from(e: io::Error) -> Self {
+        CrateSpecError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for CrateSpecError {
+    fn from(e: toml::de::Error) -> Self {
+        CrateSpecError::Toml(e.to_string())
+    }
+}
+
+impl From<DecodeError> for CrateSpecError {
+    fn from(e: DecodeError) -> Self {
+        match e {
+            DecodeError::Other(s) => CrateSpecError::Format(s.to_string()),
+            DecodeError::OtherString(s) => CrateSpecError::Format(s),
+            e => CrateSpecError::Format(format!("file format not right! - {}", e)),
+        }
+    }
+}
+
+impl From<EncodeError> for CrateSpecError {
+    fn from(e: EncodeError) -> Self {
+        match e {
+            EncodeError::Io { inner, .. } => CrateSpecError::Io(inner),
+            EncodeError::Other(s) => CrateSpecError::Format(s.to_string()),
+            EncodeError::OtherString(s) => CrateSpecError::Format(s),
+            e => CrateSpecError::Format(format!("file format not right! - {}", e)),
+        }
+    }
+}
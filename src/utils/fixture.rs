@@ -0,0 +1,54 @@
+//!packages and signers shared by tests
+use crate::utils::context::PackageContext;
+use crate::utils::pkcs::PKCS;
+
+///"rust-crate" 1.0.0 under MIT, its crate binary is crate_bin_len bytes of 15
+pub fn package_context(crate_bin_len: usize) -> PackageContext {
+    let mut package_context = PackageContext::new();
+    package_context.set_package_info(
+        "rust-crate".to_string(),
+        "1.0.0".to_string(),
+        "MIT".to_string(),
+        vec![],
+    );
+    package_context.add_crate_bin(vec![15; crate_bin_len]);
+    package_context
+}
+
+///signs with cert_path and key_path, test/root-ca.pem is its root-ca
+pub fn signer(cert_path: &str, key_path: &str) -> PKCS {
+    let mut pkcs = PKCS::new();
+    pkcs.load_from_file_writer(
+        cert_path.to_string(),
+        key_path.to_string(),
+        ["test/root-ca.pem".to_string()].to_vec(),
+    )
+    .unwrap();
+    pkcs
+}
+
+///signs with test/cert.pem
+pub fn cert_signer() -> PKCS {
+    signer("test/cert.pem", "test/key.pem")
+}
+
+pub fn root_cas() -> Vec<Vec<u8>> {
+    PKCS::root_ca_bins(["test/root-ca.pem".to_string()].to_vec()).unwrap()
+}
+
+///context to decode into, certificate signatures are verified against root_cas
+pub fn verifier() -> PackageContext {
+    let mut package_context = PackageContext::new();
+    package_context.set_root_cas_bin(root_cas());
+    package_context
+}
+
+///encode package_context at its crate version and decode it with verifier
+pub fn round_trip(package_context: &mut PackageContext) -> PackageContext {
+    let (_, _, bin) = package_context.encode_to_crate_package().unwrap();
+    let mut package_context_new = verifier();
+    package_context_new
+        .decode_from_crate_package(bin.as_slice())
+        .unwrap();
+    package_context_new
+}
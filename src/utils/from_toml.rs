@@ -1,29 +1,489 @@
-use crate::utils::context::{DepInfo, PackageContext, SrcTypePath};
+use crate::utils::context::{
+    DepInfo, DepKind, GitReference, LockInfo, PackageContext, SrcTypePath,
+};
+use crate::utils::error::CrateSpecError;
 use std::collections::HashSet;
 use std::fs;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
-use toml::Table;
+use toml::{Table, Value};
+
+///dependency tables of the manifest and of each [target.<platform>]
+const DEP_TABLES: [(&str, DepKind); 3] = [
+    ("dependencies", DepKind::Normal),
+    ("dev-dependencies", DepKind::Dev),
+    ("build-dependencies", DepKind::Build),
+];
 
 #[derive(Default)]
 pub struct CrateToml {
     t: Table,
+    ///used to find the workspace root
+    manifest_path: Option<PathBuf>,
+    ///[workspace] of the workspace root, `workspace = true` keys are resolved from it
+    workspace: Option<Table>,
+    ///directory of the workspace root set by set_workspace_root, if known
+    workspace_dir: Option<PathBuf>,
 }
 
 impl CrateToml {
-    pub fn from_file(path: String) -> CrateToml {
-        let f = fs::read(Path::new(path.as_str())).unwrap();
-        CrateToml::from_vec(f)
+    pub fn from_file(path: String) -> Result<CrateToml, CrateSpecError> {
+        let f = fs::read(Path::new(path.as_str()))?;
+        let mut crate_toml = CrateToml::from_vec(f)?;
+        crate_toml.manifest_path = Some(fs::canonicalize(path)?);
+        Ok(crate_toml)
+    }
+
+    pub fn from_vec(st_vec: Vec<u8>) -> Result<CrateToml, CrateSpecError> {
+        let st = String::from_utf8(st_vec)
+            .map_err(|_| CrateSpecError::Toml("Cargo.toml is not utf-8".to_string()))?;
+        CrateToml::from_string(st.as_str())
+    }
+
+    pub fn from_string(st: &str) -> Result<CrateToml, CrateSpecError> {
+        Ok(CrateToml {
+            t: Table::from_str(st)?,
+            ..Default::default()
+        })
+    }
+
+    ///use root's [workspace] instead of searching the parent directories of the manifest
+    pub fn set_workspace_root(&mut self, root: &CrateToml) -> Result<(), CrateSpecError> {
+        let workspace = root
+            .t
+            .get("workspace")
+            .and_then(|x| x.as_table())
+            .ok_or_else(|| CrateSpecError::Toml("[workspace] not found".to_string()))?;
+        self.workspace = Some(workspace.clone());
+        self.workspace_dir = root.manifest_dir();
+        Ok(())
+    }
+
+    fn manifest_dir(&self) -> Option<PathBuf> {
+        self.manifest_path
+            .as_ref()
+            .and_then(|manifest_path| manifest_path.parent())
+            .map(Path::to_path_buf)
+    }
+
+    ///[workspace] and directory of the manifest itself, package.workspace
+    ///or the nearest parent manifest with [workspace]
+    fn find_workspace(&self) -> Result<(Option<PathBuf>, Table), CrateSpecError> {
+        if let Some(workspace) = &self.workspace {
+            return Ok((self.workspace_dir.clone(), workspace.clone()));
+        }
+        if let Some(workspace) = self.t.get("workspace").and_then(|x| x.as_table()) {
+            return Ok((self.manifest_dir(), workspace.clone()));
+        }
+        self.find_workspace_root()?
+            .map(|(root_path, workspace)| (root_path.parent().map(Path::to_path_buf), workspace))
+            .ok_or_else(|| CrateSpecError::Toml("workspace root not found".to_string()))
+    }
+
+    ///path and [workspace] of the workspace root manifest, none if the crate is not in a workspace
+    fn find_workspace_root(&self) -> Result<Option<(PathBuf, Table)>, CrateSpecError> {
+        let manifest_path = match &self.manifest_path {
+            Some(manifest_path) => manifest_path,
+            None => return Ok(None),
+        };
+        if let Some(workspace) = self.t.get("workspace").and_then(|x| x.as_table()) {
+            return Ok(Some((manifest_path.clone(), workspace.clone())));
+        }
+        let manifest_dir = manifest_path
+            .parent()
+            .ok_or_else(|| CrateSpecError::Toml("workspace root not found".to_string()))?;
+        let root_path = self
+            .t
+            .get("package")
+            .and_then(|x| x.as_table())
+            .and_then(|package| package.get("workspace"))
+            .and_then(|x| x.as_str());
+        let candidates: Vec<PathBuf> = match root_path {
+            Some(root_path) => vec![manifest_dir.join(root_path).join("Cargo.toml")],
+            None => manifest_dir
+                .ancestors()
+                .skip(1)
+                .map(|dir| dir.join("Cargo.toml"))
+                .collect(),
+        };
+        for candidate in candidates {
+            if !candidate.is_file() {
+                continue;
+            }
+            let root = CrateToml::from_vec(fs::read(&candidate)?)?;
+            if let Some(workspace) = root.t.get("workspace").and_then(|x| x.as_table()) {
+                return Ok(Some((fs::canonicalize(candidate)?, workspace.clone())));
+            }
+        }
+        Ok(None)
+    }
+
+    ///directory cargo writes Cargo.lock and target into, the workspace root or the crate itself
+    pub fn workspace_dir(&self) -> Result<PathBuf, CrateSpecError> {
+        let manifest_path = match self.find_workspace_root()? {
+            Some((root_path, _)) => root_path,
+            None => self
+                .manifest_path
+                .clone()
+                .ok_or_else(|| CrateSpecError::Toml("manifest path not known".to_string()))?,
+        };
+        Ok(manifest_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default())
+    }
+
+    ///copy of the manifest with `workspace = true` package keys and dependencies resolved
+    fn inherit_workspace(&self) -> Result<CrateToml, CrateSpecError> {
+        let (workspace_dir, workspace) = self.find_workspace()?;
+        let member_dir = self.manifest_dir();
+        let mut t = self.t.clone();
+        if let Some(package) = t.get_mut("package").and_then(|x| x.as_table_mut()) {
+            inherit_package(package, &workspace)?;
+        }
+        let rebase =
+            |path: &str| rebase_path(path, workspace_dir.as_deref(), member_dir.as_deref());
+        for_each_dep_table(&mut t, &mut |deps| inherit_deps(deps, &workspace, &rebase))?;
+        Ok(CrateToml {
+            t,
+            ..Default::default()
+        })
+    }
+}
+
+fn is_inherited(val: &Value) -> bool {
+    val.as_table()
+        .and_then(|x| x.get("workspace"))
+        .and_then(|x| x.as_bool())
+        == Some(true)
+}
+
+fn uses_workspace(t: &Table) -> bool {
+    let package_inherits = t
+        .get("package")
+        .and_then(|x| x.as_table())
+        .is_some_and(|package| package.values().any(is_inherited));
+    package_inherits
+        || dep_tables(t)
+            .iter()
+            .any(|deps| deps.values().any(is_inherited))
+}
+
+fn dep_tables(t: &Table) -> Vec<&Table> {
+    let mut tables: Vec<&Table> = DEP_TABLES
+        .iter()
+        .filter_map(|(key, _)| t.get(*key).and_then(|x| x.as_table()))
+        .collect();
+    if let Some(targets) = t.get("target").and_then(|x| x.as_table()) {
+        for target in targets.values().filter_map(|x| x.as_table()) {
+            tables.extend(dep_tables(target));
+        }
+    }
+    tables
+}
+
+fn for_each_dep_table(
+    t: &mut Table,
+    f: &mut impl FnMut(&mut Table) -> Result<(), CrateSpecError>,
+) -> Result<(), CrateSpecError> {
+    for (key, _) in DEP_TABLES {
+        if let Some(deps) = t.get_mut(key).and_then(|x| x.as_table_mut()) {
+            f(deps)?;
+        }
+    }
+    if let Some(targets) = t.get_mut("target").and_then(|x| x.as_table_mut()) {
+        for (_, target) in targets.iter_mut() {
+            if let Some(target) = target.as_table_mut() {
+                for_each_dep_table(target, f)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+///`key.workspace = true` takes workspace.package.key
+fn inherit_package(package: &mut Table, workspace: &Table) -> Result<(), CrateSpecError> {
+    let ws_package = workspace.get("package").and_then(|x| x.as_table());
+    for (key, val) in package.iter_mut() {
+        if !is_inherited(val) {
+            continue;
+        }
+        *val = ws_package
+            .and_then(|ws_package| ws_package.get(key))
+            .cloned()
+            .ok_or_else(|| CrateSpecError::Toml(format!("workspace.package.{} not found", key)))?;
+    }
+    Ok(())
+}
+
+///path of workspace.dependencies is relative to the workspace root, rebase it to the member,
+///it is kept as written if either directory is unknown
+fn rebase_path(path: &str, workspace_dir: Option<&Path>, member_dir: Option<&Path>) -> String {
+    let (workspace_dir, member_dir) = match (workspace_dir, member_dir) {
+        (Some(workspace_dir), Some(member_dir)) => (workspace_dir, member_dir),
+        _ => return path.to_string(),
+    };
+    let target = normalize_path(&workspace_dir.join(path));
+    let base = normalize_path(member_dir);
+    let target: Vec<Component> = target.components().collect();
+    let base: Vec<Component> = base.components().collect();
+    let common = target
+        .iter()
+        .zip(base.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut rebased = PathBuf::new();
+    for _ in common..base.len() {
+        rebased.push("..");
+    }
+    rebased.extend(&target[common..]);
+    if rebased.as_os_str().is_empty() {
+        rebased.push(".");
+    }
+    rebased.to_string_lossy().to_string()
+}
+
+///drop `.` and resolve `..` without touching the file system
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push("..");
+                }
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+///`dep = { workspace = true }` takes workspace.dependencies.dep, its path rebased to the member,
+///features are added to the inherited ones and the other keys override them
+fn inherit_deps(
+    deps: &mut Table,
+    workspace: &Table,
+    rebase: &impl Fn(&str) -> String,
+) -> Result<(), CrateSpecError> {
+    let ws_deps = workspace.get("dependencies").and_then(|x| x.as_table());
+    for (name, dep) in deps.iter_mut() {
+        let member_dep = match dep.as_table() {
+            Some(member_dep) if is_inherited(dep) => member_dep.clone(),
+            _ => continue,
+        };
+        let not_found =
+            || CrateSpecError::Toml(format!("workspace.dependencies.{} not found", name));
+        let mut resolved = match ws_deps
+            .and_then(|ws_deps| ws_deps.get(name))
+            .ok_or_else(not_found)?
+        {
+            Value::String(ver_req) => {
+                Table::from_iter([("version".to_string(), Value::String(ver_req.clone()))])
+            }
+            Value::Table(ws_dep) => ws_dep.clone(),
+            _ => {
+                return Err(CrateSpecError::Toml(format!(
+                    "workspace.dependencies.{} not right",
+                    name
+                )))
+            }
+        };
+        if let Some(path) = toml_str(&resolved, "path")? {
+            resolved.insert("path".to_string(), Value::String(rebase(&path)));
+        }
+        let mut features = toml_str_array(&resolved, "features")?;
+        for feature in toml_str_array(&member_dep, "features")? {
+            if !features.contains(&feature) {
+                features.push(feature);
+            }
+        }
+        for (key, val) in member_dep.iter() {
+            if key != "workspace" && key != "features" {
+                resolved.insert(key.to_string(), val.clone());
+            }
+        }
+        if !features.is_empty() {
+            resolved.insert(
+                "features".to_string(),
+                Value::Array(features.into_iter().map(Value::String).collect()),
+            );
+        }
+        *dep = Value::Table(resolved);
+    }
+    Ok(())
+}
+
+///Cargo.lock of the project being packed
+#[derive(Default)]
+pub struct CrateLock {
+    t: Table,
+}
+
+impl CrateLock {
+    pub fn from_file(path: String) -> Result<CrateLock, CrateSpecError> {
+        let f = fs::read(Path::new(path.as_str()))?;
+        let st = String::from_utf8(f)
+            .map_err(|_| CrateSpecError::Toml("Cargo.lock is not utf-8".to_string()))?;
+        CrateLock::from_string(st.as_str())
+    }
+
+    pub fn from_string(st: &str) -> Result<CrateLock, CrateSpecError> {
+        Ok(CrateLock {
+            t: Table::from_str(st)?,
+        })
     }
 
-    pub fn from_vec(st_vec: Vec<u8>) -> CrateToml {
-        CrateToml::from_string(String::from_utf8(st_vec).unwrap().as_str())
+    ///[[package]] entries, in the order of Cargo.lock
+    pub fn write_info_to_package_context(
+        &self,
+        package_context: &mut PackageContext,
+    ) -> Result<(), CrateSpecError> {
+        package_context.lock_infos.extend(self.lock_infos()?);
+        Ok(())
     }
 
-    pub fn from_string(st: &str) -> CrateToml {
-        CrateToml {
-            t: Table::from_str(st).unwrap(),
+    ///[[package]] entries package_context's crate resolves, itself included,
+    ///so that a workspace lockfile does not bring in the packages of other members
+    pub fn write_resolved_to_package_context(
+        &self,
+        package_context: &mut PackageContext,
+    ) -> Result<(), CrateSpecError> {
+        let lock_infos = self.lock_infos()?;
+        let root = lock_infos
+            .iter()
+            .position(|lock_info| {
+                lock_info.source.is_empty()
+                    && lock_info.name == package_context.pack_info.name
+                    && lock_info.version == package_context.pack_info.version
+            })
+            .ok_or_else(|| {
+                CrateSpecError::Toml(format!(
+                    "Cargo.lock package {} {} not found",
+                    package_context.pack_info.name, package_context.pack_info.version
+                ))
+            })?;
+        let mut resolved = vec![false; lock_infos.len()];
+        resolved[root] = true;
+        let mut pending = vec![root];
+        while let Some(id) = pending.pop() {
+            for dep in lock_infos[id].dependencies.iter() {
+                for (dep_id, lock_info) in lock_infos.iter().enumerate() {
+                    if !resolved[dep_id] && is_lock_dep(dep, lock_info) {
+                        resolved[dep_id] = true;
+                        pending.push(dep_id);
+                    }
+                }
+            }
         }
+        package_context.lock_infos.extend(
+            lock_infos
+                .into_iter()
+                .zip(resolved)
+                .filter_map(|(lock_info, resolved)| resolved.then_some(lock_info)),
+        );
+        Ok(())
+    }
+
+    fn lock_infos(&self) -> Result<Vec<LockInfo>, CrateSpecError> {
+        let packages = match self.t.get("package") {
+            None => return Ok(vec![]),
+            Some(packages) => packages.as_array().ok_or_else(|| {
+                CrateSpecError::Toml("Cargo.lock [[package]] not right".to_string())
+            })?,
+        };
+        let mut lock_infos = vec![];
+        for package in packages {
+            let package = package.as_table().ok_or_else(|| {
+                CrateSpecError::Toml("Cargo.lock [[package]] not right".to_string())
+            })?;
+            let not_found =
+                |key: &str| CrateSpecError::Toml(format!("Cargo.lock package.{} not found", key));
+            lock_infos.push(LockInfo {
+                name: toml_str(package, "name")?.ok_or_else(|| not_found("name"))?,
+                version: toml_str(package, "version")?.ok_or_else(|| not_found("version"))?,
+                source: toml_str(package, "source")?.unwrap_or_default(),
+                checksum: toml_str(package, "checksum")?.unwrap_or_default(),
+                dependencies: toml_str_array(package, "dependencies")?,
+            });
+        }
+        Ok(lock_infos)
+    }
+}
+
+///dep is "name", "name version" or "name version (source)", cargo only adds what is ambiguous
+fn is_lock_dep(dep: &str, lock_info: &LockInfo) -> bool {
+    let mut parts = dep.splitn(3, ' ');
+    parts.next() == Some(lock_info.name.as_str())
+        && parts
+            .next()
+            .is_none_or(|version| version == lock_info.version)
+        && parts.next().is_none_or(|source| {
+            source.trim_start_matches('(').trim_end_matches(')') == lock_info.source
+        })
+}
+
+fn toml_str(table: &Table, key: &str) -> Result<Option<String>, CrateSpecError> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(val) => match val.as_str() {
+            Some(st) => Ok(Some(st.to_string())),
+            None => Err(CrateSpecError::Toml(format!("{} should be a string", key))),
+        },
+    }
+}
+
+fn toml_bool(table: &Table, key: &str) -> Result<Option<bool>, CrateSpecError> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(val) => match val.as_bool() {
+            Some(b) => Ok(Some(b)),
+            None => Err(CrateSpecError::Toml(format!("{} should be a bool", key))),
+        },
+    }
+}
+
+fn toml_str_array(table: &Table, key: &str) -> Result<Vec<String>, CrateSpecError> {
+    let not_str_array = || CrateSpecError::Toml(format!("{} should be an array of strings", key));
+    match table.get(key) {
+        None => Ok(vec![]),
+        Some(val) => val
+            .as_array()
+            .ok_or_else(not_str_array)?
+            .iter()
+            .map(|x| x.as_str().map(|x| x.to_string()).ok_or_else(not_str_array))
+            .collect(),
+    }
+}
+
+///readme is a path, or true for README.md, or false for none
+fn toml_readme(package: &Table) -> Result<String, CrateSpecError> {
+    match package.get("readme").and_then(|val| val.as_bool()) {
+        Some(true) => Ok("README.md".to_string()),
+        Some(false) => Ok("".to_string()),
+        None => Ok(toml_str(package, "readme")?.unwrap_or_default()),
+    }
+}
+
+///at most one of rev, branch and tag
+fn toml_git_reference(dep: &Table) -> Result<GitReference, CrateSpecError> {
+    let mut references = vec![];
+    if let Some(rev) = toml_str(dep, "rev")? {
+        references.push(GitReference::Rev(rev));
+    }
+    if let Some(branch) = toml_str(dep, "branch")? {
+        references.push(GitReference::Branch(branch));
+    }
+    if let Some(tag) = toml_str(dep, "tag")? {
+        references.push(GitReference::Tag(tag));
+    }
+    match references.len() {
+        0 => Ok(GitReference::DefaultBranch),
+        1 => Ok(references.remove(0)),
+        _ => Err(CrateSpecError::Toml(
+            "only one of rev, branch and tag can be specified".to_string(),
+        )),
     }
 }
 
@@ -32,23 +492,62 @@ impl CrateToml {
         &self,
         package_context: &mut PackageContext,
         package: &Table,
-    ) {
-        let name = package["name"].as_str().unwrap().to_string();
-        let version = package["version"].as_str().unwrap().to_string();
-        let mut license = "".to_string();
-        let mut authors = Vec::<String>::new();
-        if package.contains_key("license") {
-            license = package["license"].as_str().unwrap().to_string();
-        }
-        if package.contains_key("authors") {
-            authors = package["authors"]
-                .as_array()
-                .unwrap()
-                .iter()
-                .map(|x| x.as_str().unwrap().to_string())
-                .collect();
-        }
+    ) -> Result<(), CrateSpecError> {
+        let name = toml_str(package, "name")?
+            .ok_or_else(|| CrateSpecError::Toml("package.name not found".to_string()))?;
+        let version = toml_str(package, "version")?
+            .ok_or_else(|| CrateSpecError::Toml("package.version not found".to_string()))?;
+        let license = toml_str(package, "license")?.unwrap_or_default();
+        let authors = toml_str_array(package, "authors")?;
         package_context.set_package_info(name, version, license, authors);
+
+        let pack_info = &mut package_context.pack_info;
+        pack_info.description = toml_str(package, "description")?.unwrap_or_default();
+        pack_info.homepage = toml_str(package, "homepage")?.unwrap_or_default();
+        pack_info.repository = toml_str(package, "repository")?.unwrap_or_default();
+        pack_info.documentation = toml_str(package, "documentation")?.unwrap_or_default();
+        pack_info.readme = toml_readme(package)?;
+        pack_info.keywords = toml_str_array(package, "keywords")?;
+        pack_info.categories = toml_str_array(package, "categories")?;
+        pack_info.edition = toml_str(package, "edition")?.unwrap_or_default();
+        pack_info.rust_version = toml_str(package, "rust-version")?.unwrap_or_default();
+        pack_info.links = toml_str(package, "links")?.unwrap_or_default();
+        pack_info.license_file = toml_str(package, "license-file")?.unwrap_or_default();
+        Ok(())
+    }
+
+    ///optional dependency not named by any `dep:` activation gets an implicit feature
+    fn write_feature_info_to_package_context(
+        &self,
+        package_context: &mut PackageContext,
+    ) -> Result<(), CrateSpecError> {
+        if let Some(features) = self.t.get("features") {
+            let features = features
+                .as_table()
+                .ok_or_else(|| CrateSpecError::Toml("[features] not right".to_string()))?;
+            for feature in features.keys() {
+                let activations = toml_str_array(features, feature)?;
+                package_context.add_feature_info(feature.to_string(), activations);
+            }
+        }
+
+        //dev-dependencies can not be optional, so this covers normal and build ones
+        for deps in dep_tables(&self.t) {
+            for (name, val) in deps.iter() {
+                let optional = val
+                    .get("optional")
+                    .and_then(|x| x.as_bool())
+                    .unwrap_or(false);
+                let dep_activation = format!("dep:{}", name);
+                let named = package_context.feature_infos.iter().any(|feature| {
+                    feature.name == *name || feature.activations.contains(&dep_activation)
+                });
+                if optional && !named {
+                    package_context.add_feature_info(name.to_string(), vec![dep_activation]);
+                }
+            }
+        }
+        Ok(())
     }
 
     fn write_dep_info_to_package_context(
@@ -56,80 +555,605 @@ impl CrateToml {
         package_context: &mut PackageContext,
         deps: &Table,
         platform: String,
-    ) -> Vec<String> {
+        kind: DepKind,
+    ) -> Result<Vec<String>, CrateSpecError> {
         let mut irresolve_depinfos = vec![];
         for dep in deps.iter() {
             let mut dep_info = DepInfo {
                 src_platform: platform.to_string(),
                 name: dep.0.to_string(),
+                kind,
                 ..Default::default()
             };
             let val = dep.1;
-            if val.is_str() {
-                dep_info.ver_req = val.as_str().unwrap().to_string();
+            if let Some(ver_req) = val.as_str() {
+                dep_info.ver_req = ver_req.to_string();
             } else {
-                let attri_map = val.as_table().unwrap();
+                let attri_map = val.as_table().ok_or_else(|| {
+                    CrateSpecError::Toml(format!("dependency {} not right", dep.0))
+                })?;
                 let allow_keys = HashSet::from([
                     "version".to_string(),
                     "git".to_string(),
+                    "rev".to_string(),
+                    "branch".to_string(),
+                    "tag".to_string(),
+                    "path".to_string(),
                     "registry".to_string(),
+                    "optional".to_string(),
+                    "default-features".to_string(),
+                    "default_features".to_string(),
+                    "features".to_string(),
+                    "package".to_string(),
                 ]);
                 for attri in attri_map.keys() {
                     if !allow_keys.contains(attri) {
                         dep_info.dump = false;
                     }
                 }
-                if attri_map.contains_key("version") {
-                    dep_info.ver_req = attri_map["version"].as_str().unwrap().to_string();
+                if let Some(ver_req) = toml_str(attri_map, "version")? {
+                    dep_info.ver_req = ver_req;
                 }
-                if attri_map.contains_key("git") {
-                    dep_info.src = SrcTypePath::Git(attri_map["git"].as_str().unwrap().to_string());
+                if let Some(path) = toml_str(attri_map, "path")? {
+                    dep_info.src = SrcTypePath::Path(path);
                 }
-                if attri_map.contains_key("registry") {
-                    dep_info.src =
-                        SrcTypePath::Registry(attri_map["registry"].as_str().unwrap().to_string());
+                if let Some(url) = toml_str(attri_map, "git")? {
+                    dep_info.src = SrcTypePath::Git {
+                        url,
+                        reference: toml_git_reference(attri_map)?,
+                    };
+                }
+                if let Some(registry) = toml_str(attri_map, "registry")? {
+                    dep_info.src = SrcTypePath::Registry(registry);
+                }
+                if let Some(optional) = toml_bool(attri_map, "optional")? {
+                    dep_info.optional = optional;
+                }
+                if let Some(default_features) = toml_bool(attri_map, "default-features")?
+                    .or(toml_bool(attri_map, "default_features")?)
+                {
+                    dep_info.default_features = default_features;
+                }
+                dep_info.features = toml_str_array(attri_map, "features")?;
+                //renamed dependency keeps its local name in name
+                if let Some(package) = toml_str(attri_map, "package")? {
+                    dep_info.package = package;
                 }
             }
             if dep_info.dump {
-                package_context.add_dep_info(
-                    dep_info.name,
-                    dep_info.ver_req,
-                    dep_info.src,
-                    dep_info.src_platform,
-                );
+                package_context.dep_infos.push(dep_info);
             } else {
                 irresolve_depinfos.push(dep_info.name);
             }
         }
-        irresolve_depinfos
+        Ok(irresolve_depinfos)
     }
 
     pub fn write_info_to_package_context(
         &self,
         package_context: &mut PackageContext,
-    ) -> Vec<String> {
-        assert!(self.t.contains_key("package"));
-        self.write_package_info_to_package_context(
-            package_context,
-            self.t.get("package").unwrap().as_table().unwrap(),
-        );
-        //FIXME current platform is not considered, we only consider [dependencies], see https://course.rs/cargo/reference/specify-deps.html#build-dependencies
-        let excluded_crate = self.write_dep_info_to_package_context(
-            package_context,
-            self.t.get("dependencies").unwrap().as_table().unwrap(),
-            "".to_string(),
-        );
-        excluded_crate
+    ) -> Result<Vec<String>, CrateSpecError> {
+        if uses_workspace(&self.t) {
+            return self
+                .inherit_workspace()?
+                .write_info_to_package_context(package_context);
+        }
+        let package = self
+            .t
+            .get("package")
+            .and_then(|x| x.as_table())
+            .ok_or_else(|| CrateSpecError::Toml("[package] not found".to_string()))?;
+        self.write_package_info_to_package_context(package_context, package)?;
+        self.write_feature_info_to_package_context(package_context)?;
+        //platform-specific dependencies record their target triple or cfg expression,
+        //see https://doc.rust-lang.org/cargo/reference/specifying-dependencies.html#platform-specific-dependencies
+        let mut irresolve_depinfos =
+            self.write_deps_to_package_context(package_context, &self.t, "")?;
+        if let Some(targets) = self.t.get("target") {
+            let targets = targets
+                .as_table()
+                .ok_or_else(|| CrateSpecError::Toml("[target] not right".to_string()))?;
+            for (platform, target) in targets.iter() {
+                let target = target.as_table().ok_or_else(|| {
+                    CrateSpecError::Toml(format!("[target.{}] not right", platform))
+                })?;
+                irresolve_depinfos.extend(self.write_deps_to_package_context(
+                    package_context,
+                    target,
+                    platform,
+                )?);
+            }
+        }
+        package_context
+            .unrepresented_deps
+            .extend(irresolve_depinfos.iter().cloned());
+        Ok(irresolve_depinfos)
+    }
+
+    ///dependencies, dev-dependencies and build-dependencies of table
+    fn write_deps_to_package_context(
+        &self,
+        package_context: &mut PackageContext,
+        table: &Table,
+        platform: &str,
+    ) -> Result<Vec<String>, CrateSpecError> {
+        let mut irresolve_depinfos = vec![];
+        for (key, kind) in DEP_TABLES {
+            let deps = match table.get(key) {
+                None => continue,
+                Some(deps) => deps
+                    .as_table()
+                    .ok_or_else(|| CrateSpecError::Toml(format!("[{}] not right", key)))?,
+            };
+            irresolve_depinfos.extend(self.write_dep_info_to_package_context(
+                package_context,
+                deps,
+                platform.to_string(),
+                kind,
+            )?);
+        }
+        Ok(irresolve_depinfos)
     }
 }
 
 #[test]
 fn test_toml() {
-    let toml = CrateToml::from_file("test/test.toml".to_string());
+    let toml = CrateToml::from_file("test/test.toml".to_string()).unwrap();
     let mut pack_context = PackageContext::new();
     println!(
         "{:?}",
         toml.write_info_to_package_context(&mut pack_context)
+            .unwrap()
     );
     println!("{:#?}", pack_context);
 }
+
+///test/test.toml written to a package context
+#[cfg(test)]
+fn test_toml_context() -> PackageContext {
+    let toml = CrateToml::from_file("test/test.toml".to_string()).unwrap();
+    let mut pack_context = PackageContext::new();
+    toml.write_info_to_package_context(&mut pack_context)
+        .unwrap();
+    pack_context
+}
+
+#[cfg(test)]
+fn find_dep<'a>(pack_context: &'a PackageContext, name: &str, kind: DepKind) -> &'a DepInfo {
+    pack_context
+        .dep_infos
+        .iter()
+        .find(|dep_info| dep_info.name == name && dep_info.kind == kind)
+        .unwrap()
+}
+
+#[test]
+fn test_toml_metadata() {
+    let pack_context = test_toml_context();
+    let pack_info = &pack_context.pack_info;
+    assert_eq!("crate package spec", pack_info.description);
+    assert_eq!(
+        "https://github.com/open-rust-initiative/crate-spec",
+        pack_info.repository
+    );
+    //readme = true is the default README.md
+    assert_eq!("README.md", pack_info.readme);
+    assert_eq!(["crate", "sign"].to_vec(), pack_info.keywords);
+    assert_eq!(["encoding"].to_vec(), pack_info.categories);
+    assert_eq!("2021", pack_info.edition);
+    assert_eq!("1.70", pack_info.rust_version);
+    //keys not set stay empty
+    assert_eq!("", pack_info.homepage);
+    assert_eq!("", pack_info.links);
+
+    //readme = false is no readme
+    let toml =
+        CrateToml::from_string("[package]\nname = \"a\"\nversion = \"0.1.0\"\nreadme = false")
+            .unwrap();
+    let mut pack_context = PackageContext::new();
+    toml.write_info_to_package_context(&mut pack_context)
+        .unwrap();
+    assert_eq!("", pack_context.pack_info.readme);
+}
+
+#[test]
+fn test_toml_features() {
+    use crate::utils::context::FeatureActivation;
+
+    let pack_context = test_toml_context();
+    let activations = |name: &str| pack_context.feature_info(name).unwrap().activations.clone();
+    assert_eq!(["std"].to_vec(), activations("default"));
+    assert!(activations("std").is_empty());
+    //explicit feature named after an optional dependency replaces the implicit one
+    assert_eq!(
+        ["dep:serde", "toml?/preserve_order"].to_vec(),
+        activations("serde")
+    );
+    assert_eq!(
+        vec![
+            FeatureActivation::Dep("serde".to_string()),
+            FeatureActivation::DepFeature {
+                dep: "toml".to_string(),
+                feature: "preserve_order".to_string(),
+                weak: true,
+            },
+        ],
+        pack_context
+            .feature_info("serde")
+            .unwrap()
+            .parsed_activations()
+    );
+    //optional normal, build and target build dependencies get implicit features
+    for name in ["zstd", "bindgen", "vcpkg"] {
+        assert_eq!([format!("dep:{}", name)].to_vec(), activations(name));
+    }
+    assert!(pack_context.feature_info("toml").is_none());
+
+    //dep:name in a feature hides the implicit feature
+    let toml = CrateToml::from_string(
+        r#"
+[package]
+name = "a"
+version = "0.1.0"
+
+[dependencies]
+zstd = {version = "0.13", optional = true}
+
+[features]
+compress = ["dep:zstd"]
+"#,
+    )
+    .unwrap();
+    let mut pack_context = PackageContext::new();
+    toml.write_info_to_package_context(&mut pack_context)
+        .unwrap();
+    assert_eq!(
+        ["compress"].to_vec(),
+        pack_context
+            .feature_infos
+            .iter()
+            .map(|feature| feature.name.as_str())
+            .collect::<Vec<_>>()
+    );
+
+    //activations have to be a list of strings
+    let toml = CrateToml::from_string(
+        "[package]\nname = \"a\"\nversion = \"0.1.0\"\n[features]\ndefault = \"std\"",
+    )
+    .unwrap();
+    assert!(toml
+        .write_info_to_package_context(&mut PackageContext::new())
+        .is_err());
+}
+
+#[test]
+fn test_toml_dep_kind() {
+    let pack_context = test_toml_context();
+    find_dep(&pack_context, "toml", DepKind::Normal);
+    find_dep(&pack_context, "tempfile", DepKind::Dev);
+    find_dep(&pack_context, "cc", DepKind::Build);
+    //[target.<platform>] tables keep the kind of their table
+    find_dep(&pack_context, "schannel", DepKind::Normal);
+    find_dep(&pack_context, "pkg-config", DepKind::Build);
+
+    //one dependency in several tables is kept once per table
+    let toml = CrateToml::from_string(
+        r#"
+[package]
+name = "a"
+version = "0.1.0"
+
+[dependencies]
+toml = "0.7"
+
+[dev-dependencies]
+toml = {version = "0.7", features = ["preserve_order"]}
+"#,
+    )
+    .unwrap();
+    let mut pack_context = PackageContext::new();
+    toml.write_info_to_package_context(&mut pack_context)
+        .unwrap();
+    assert_eq!(2, pack_context.dep_infos.len());
+    assert!(find_dep(&pack_context, "toml", DepKind::Normal)
+        .features
+        .is_empty());
+    assert_eq!(
+        ["preserve_order"].to_vec(),
+        find_dep(&pack_context, "toml", DepKind::Dev).features
+    );
+}
+
+#[test]
+fn test_toml_dep_platform() {
+    let pack_context = test_toml_context();
+    let platform =
+        |name: &str, kind: DepKind| find_dep(&pack_context, name, kind).src_platform.clone();
+    assert_eq!("", platform("toml", DepKind::Normal));
+    assert_eq!("", platform("cc", DepKind::Build));
+    assert_eq!("cfg(windows)", platform("schannel", DepKind::Normal));
+    assert_eq!(
+        "cfg(not(windows))",
+        platform("openssl-sys", DepKind::Normal)
+    );
+    assert_eq!(
+        "x86_64-unknown-linux-gnu",
+        platform("pkg-config", DepKind::Build)
+    );
+
+    //[target.<platform>] has to be a table
+    let toml = CrateToml::from_string(
+        "[package]\nname = \"a\"\nversion = \"0.1.0\"\n[target]\nunix = \"libc\"",
+    )
+    .unwrap();
+    assert!(toml
+        .write_info_to_package_context(&mut PackageContext::new())
+        .is_err());
+}
+
+#[test]
+fn test_toml_dep_attributes() {
+    let pack_context = test_toml_context();
+    let dep_info = |name: &str| find_dep(&pack_context, name, DepKind::Normal);
+    assert_eq!(["serde", "alloc"].to_vec(), dep_info("bincode").features);
+    assert!(dep_info("serde").optional);
+    assert!(!dep_info("rand_old").default_features);
+    //renamed dependency keeps its local name in name
+    assert_eq!("rand", dep_info("rand_old").package);
+    let toml = dep_info("toml");
+    assert!(!toml.optional);
+    assert!(toml.default_features);
+    assert!(toml.features.is_empty());
+    assert_eq!("", toml.package);
+
+    //default_features is the older spelling of default-features
+    let toml = CrateToml::from_string(
+        r#"
+[package]
+name = "a"
+version = "0.1.0"
+
+[dependencies]
+rand = {version = "0.8", default_features = false}
+"#,
+    )
+    .unwrap();
+    let mut pack_context = PackageContext::new();
+    toml.write_info_to_package_context(&mut pack_context)
+        .unwrap();
+    assert!(!pack_context.dep_infos[0].default_features);
+
+    let toml = CrateToml::from_string(
+        r#"
+[package]
+name = "a"
+version = "0.1.0"
+
+[dependencies]
+rand = {version = "0.8", optional = "yes"}
+"#,
+    )
+    .unwrap();
+    assert!(toml
+        .write_info_to_package_context(&mut PackageContext::new())
+        .is_err());
+}
+
+#[test]
+fn test_toml_dep_source() {
+    let pack_context = test_toml_context();
+    let dep_info = |name: &str| find_dep(&pack_context, name, DepKind::Normal);
+    assert_eq!(SrcTypePath::CratesIo, dep_info("toml").src);
+    assert_eq!(
+        SrcTypePath::Registry("rust".to_string()),
+        dep_info("openssl").src
+    );
+    assert_eq!(
+        SrcTypePath::Git {
+            url: "https://github.com/rust-lang/regex.git".to_string(),
+            reference: GitReference::Rev("9f9f693".to_string()),
+        },
+        dep_info("regex").src
+    );
+    assert_eq!(
+        SrcTypePath::Git {
+            url: "https://github.com/rust-lang/log.git".to_string(),
+            reference: GitReference::DefaultBranch,
+        },
+        dep_info("log").src
+    );
+    assert_eq!(
+        SrcTypePath::Path("../utils".to_string()),
+        dep_info("utils").src
+    );
+    assert_eq!("0.1", dep_info("utils").ver_req);
+
+    let toml = CrateToml::from_string(
+        r#"
+[package]
+name = "a"
+version = "0.1.0"
+
+[dependencies]
+log = {git = "https://github.com/rust-lang/log.git", branch = "main"}
+regex = {git = "https://github.com/rust-lang/regex.git", tag = "1.10.0"}
+"#,
+    )
+    .unwrap();
+    let mut pack_context = PackageContext::new();
+    toml.write_info_to_package_context(&mut pack_context)
+        .unwrap();
+    let reference = |name: &str| match &find_dep(&pack_context, name, DepKind::Normal).src {
+        SrcTypePath::Git { reference, .. } => reference.clone(),
+        _ => panic!("{} is not a git dependency", name),
+    };
+    assert_eq!(GitReference::Branch("main".to_string()), reference("log"));
+    assert_eq!(GitReference::Tag("1.10.0".to_string()), reference("regex"));
+
+    //only one of rev, branch and tag
+    let toml = CrateToml::from_string(
+        r#"
+[package]
+name = "a"
+version = "0.1.0"
+
+[dependencies]
+log = {git = "https://github.com/rust-lang/log.git", branch = "main", tag = "0.4.20"}
+"#,
+    )
+    .unwrap();
+    assert!(toml
+        .write_info_to_package_context(&mut PackageContext::new())
+        .is_err());
+}
+
+#[test]
+fn test_toml_unrepresented() {
+    let toml = CrateToml::from_file("test/test.toml".to_string()).unwrap();
+    let mut pack_context = PackageContext::new();
+    let irresolve_depinfos = toml
+        .write_info_to_package_context(&mut pack_context)
+        .unwrap();
+    //artifact dependency has a key the dependency table has no place for
+    assert_eq!(["tool"].to_vec(), irresolve_depinfos);
+    assert_eq!(irresolve_depinfos, pack_context.unrepresented_deps);
+    assert!(pack_context
+        .dep_infos
+        .iter()
+        .all(|dep_info| dep_info.name != "tool"));
+}
+
+#[test]
+fn test_toml_workspace() {
+    let toml = CrateToml::from_file("test/workspace/member/Cargo.toml".to_string()).unwrap();
+    let mut pack_context = PackageContext::new();
+    let irresolve_depinfos = toml
+        .write_info_to_package_context(&mut pack_context)
+        .unwrap();
+    assert!(irresolve_depinfos.is_empty());
+    assert_eq!("member", pack_context.pack_info.name);
+    assert_eq!("0.2.0", pack_context.pack_info.version);
+    assert_eq!(
+        ["shuibing", "rust"].to_vec(),
+        pack_context.pack_info.authors
+    );
+    assert_eq!("2021", pack_context.pack_info.edition);
+    assert_eq!("workspace member", pack_context.pack_info.description);
+    let dep_info = |name: &str, kind: DepKind| {
+        pack_context
+            .dep_infos
+            .iter()
+            .find(|dep_info| dep_info.name == name && dep_info.kind == kind)
+            .unwrap()
+    };
+    assert_eq!("0.7.4", dep_info("toml", DepKind::Normal).ver_req);
+    assert_eq!("0.7.4", dep_info("toml", DepKind::Dev).ver_req);
+    assert_eq!("cfg(unix)", dep_info("toml", DepKind::Dev).src_platform);
+    let serde = dep_info("serde", DepKind::Normal);
+    assert_eq!("1.0", serde.ver_req);
+    assert!(serde.optional);
+    assert!(!serde.default_features);
+    assert_eq!(["derive", "std"].to_vec(), serde.features);
+    assert_eq!(
+        ["dep:serde"].to_vec(),
+        pack_context.feature_info("serde").unwrap().activations
+    );
+    //util is "util" in the workspace root and rebased to the member
+    assert_eq!(
+        SrcTypePath::Path("../util".to_string()),
+        dep_info("util", DepKind::Normal).src
+    );
+
+    //inheriting without a workspace root is an error
+    let member = fs::read_to_string("test/workspace/member/Cargo.toml").unwrap();
+    let mut toml = CrateToml::from_string(member.as_str()).unwrap();
+    assert!(toml
+        .write_info_to_package_context(&mut PackageContext::new())
+        .is_err());
+    let root = CrateToml::from_file("test/workspace/Cargo.toml".to_string()).unwrap();
+    toml.set_workspace_root(&root).unwrap();
+    let mut pack_context = PackageContext::new();
+    toml.write_info_to_package_context(&mut pack_context)
+        .unwrap();
+    assert_eq!("0.2.0", pack_context.pack_info.version);
+}
+
+#[test]
+fn test_lock() {
+    let lock = CrateLock::from_file("test/test.lock".to_string()).unwrap();
+    let mut pack_context = PackageContext::new();
+    lock.write_info_to_package_context(&mut pack_context)
+        .unwrap();
+    assert_eq!(3, pack_context.lock_infos.len());
+    assert_eq!(
+        LockInfo {
+            name: "crate-spec".to_string(),
+            version: "0.1.0".to_string(),
+            dependencies: vec!["serde".to_string(), "toml 0.7.4".to_string()],
+            ..Default::default()
+        },
+        pack_context.lock_infos[0]
+    );
+    let toml = &pack_context.lock_infos[2];
+    assert_eq!("toml", toml.name);
+    assert_eq!(
+        "registry+https://github.com/rust-lang/crates.io-index",
+        toml.source
+    );
+    assert_eq!(
+        "d6135d499e69981f9ff0ef2167955a5333c35e36f6937d382974566b3d5b94ec",
+        toml.checksum
+    );
+    assert_eq!(["serde"].to_vec(), toml.dependencies);
+}
+
+#[test]
+fn test_lock_workspace() {
+    let toml = CrateToml::from_file("test/workspace/member/Cargo.toml".to_string()).unwrap();
+    let workspace_dir = toml.workspace_dir().unwrap();
+    assert_eq!(fs::canonicalize("test/workspace").unwrap(), workspace_dir);
+    let root = CrateToml::from_file("test/workspace/Cargo.toml".to_string()).unwrap();
+    assert_eq!(workspace_dir, root.workspace_dir().unwrap());
+
+    //sibling and the toml version only sibling uses are left out
+    let lock = CrateLock::from_file(
+        workspace_dir
+            .join("Cargo.lock")
+            .to_str()
+            .unwrap()
+            .to_string(),
+    )
+    .unwrap();
+    let mut pack_context = PackageContext::new();
+    toml.write_info_to_package_context(&mut pack_context)
+        .unwrap();
+    lock.write_resolved_to_package_context(&mut pack_context)
+        .unwrap();
+    let resolved: Vec<(&str, &str)> = pack_context
+        .lock_infos
+        .iter()
+        .map(|lock_info| (lock_info.name.as_str(), lock_info.version.as_str()))
+        .collect();
+    assert_eq!(
+        [
+            ("member", "0.2.0"),
+            ("serde", "1.0.188"),
+            ("toml", "0.7.4"),
+            ("util", "0.2.0")
+        ]
+        .to_vec(),
+        resolved
+    );
+
+    //a crate missing from the lockfile is an error
+    let mut pack_context = PackageContext::new();
+    pack_context.set_package_info(
+        "member".to_string(),
+        "0.3.0".to_string(),
+        "MIT".to_string(),
+        vec![],
+    );
+    assert!(lock
+        .write_resolved_to_package_context(&mut pack_context)
+        .is_err());
+}
@@ -0,0 +1,106 @@
+//!merkle tree over section digests
+use crate::utils::error::CrateSpecError;
+use crate::utils::package::{
+    CrateHeader, CratePackage, FingerPrintType, SectionIndex, SectionIndexEntry, FINGERPRINT_LEN,
+};
+use crate::utils::pkcs::PKCS;
+
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+fn hash_with_prefix(prefix: u8, parts: &[&[u8]]) -> Result<Vec<u8>, CrateSpecError> {
+    let mut buf = vec![prefix];
+    for part in parts {
+        buf.extend_from_slice(part);
+    }
+    PKCS::new().gen_digest_256(buf.as_slice())
+}
+
+///root of the merkle tree whose leaves are section digests,
+///an odd node at the end of a level is moved up as it is
+pub fn merkle_root(leaves: &[FingerPrintType]) -> Result<FingerPrintType, CrateSpecError> {
+    let mut root = [0; FINGERPRINT_LEN];
+    if leaves.is_empty() {
+        return Ok(root);
+    }
+    let mut level = vec![];
+    for leaf in leaves.iter() {
+        level.push(hash_with_prefix(LEAF_PREFIX, &[leaf])?);
+    }
+    while level.len() > 1 {
+        let mut next_level = vec![];
+        for pair in level.chunks(2) {
+            if pair.len() == 2 {
+                next_level.push(hash_with_prefix(NODE_PREFIX, &[&pair[0], &pair[1]])?);
+            } else {
+                next_level.push(pair[0].clone());
+            }
+        }
+        level = next_level;
+    }
+    root.copy_from_slice(level[0].as_slice());
+    Ok(root)
+}
+
+fn digest(parts: &[&[u8]]) -> Result<FingerPrintType, CrateSpecError> {
+    let mut leaf = [0; FINGERPRINT_LEN];
+    leaf.copy_from_slice(
+        PKCS::new()
+            .gen_digest_256(parts.concat().as_slice())?
+            .as_slice(),
+    );
+    Ok(leaf)
+}
+
+///binds everything a reader needs to find and decode the section, not only its bytes
+fn entry_leaf(id: usize, entry: &SectionIndexEntry) -> Result<FingerPrintType, CrateSpecError> {
+    digest(&[
+        &(id as u32).to_le_bytes(),
+        &[entry.sh_type, entry.sh_codec],
+        &entry.sh_size.to_le_bytes(),
+        &entry.sh_digest,
+    ])
+}
+
+///leaves are format version, alignment and string table digest,
+///then position, type, codec, size and digest of each none-sig section in section index
+pub fn calc_merkle_root(
+    crate_header: &CrateHeader,
+    string_table: &[u8],
+    section_index: &SectionIndex,
+) -> Result<FingerPrintType, CrateSpecError> {
+    let strtable_digest = digest(&[string_table])?;
+    let mut leaves = vec![digest(&[
+        &[crate_header.c_version, crate_header.c_align],
+        &strtable_digest,
+    ])?];
+    for (id, entry) in section_index.entries.arr.iter().enumerate() {
+        if entry.section_type() != 4 {
+            leaves.push(entry_leaf(id, entry)?);
+        }
+    }
+    merkle_root(&leaves)
+}
+
+impl CratePackage {
+    pub fn calc_merkle_root(&self) -> Result<FingerPrintType, CrateSpecError> {
+        calc_merkle_root(
+            &self.crate_header,
+            self.string_table.arr.as_slice(),
+            &self.section_index,
+        )
+    }
+}
+
+#[test]
+fn test_merkle_root() {
+    let leaves: Vec<FingerPrintType> = (0..5).map(|i| [i; FINGERPRINT_LEN]).collect();
+    let root = merkle_root(&leaves).unwrap();
+    assert_eq!(root, merkle_root(&leaves).unwrap());
+    for n in 1..leaves.len() {
+        assert_ne!(root, merkle_root(&leaves[..n]).unwrap());
+    }
+    let mut changed = leaves.clone();
+    changed[4][0] = 9;
+    assert_ne!(root, merkle_root(&changed).unwrap());
+}
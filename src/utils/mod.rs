@@ -1,6 +1,12 @@
+pub mod codec;
 pub mod context;
 pub mod decode;
 pub mod encode;
+pub mod error;
+#[cfg(test)]
+pub mod fixture;
 pub mod from_toml;
+pub mod merkle;
 pub mod package;
 pub mod pkcs;
+pub mod verify;
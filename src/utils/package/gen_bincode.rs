@@ -2,16 +2,26 @@ use bincode;
 use bincode::config::{legacy, Configuration, Fixint, LittleEndian, NoLimit};
 use bincode::de::read::{Reader, SliceReader};
 use bincode::de::{Decoder, DecoderImpl};
+use bincode::enc::write::Writer;
 use bincode::enc::Encoder;
 use bincode::{enc, BorrowDecode, Decode, Encode};
 
 use bincode::error::{DecodeError, EncodeError};
+use openssl::sha::Sha256;
+use std::any::{Any, TypeId};
+use std::io::Write;
+use std::ops::Range;
+
+use crate::utils::codec::{decode_by_codec, CODEC_NONE};
+use crate::utils::error::CrateSpecError;
 
 use crate::utils::package::{
-    CrateBinarySection, CrateHeader, CratePackage, DataSection, DataSectionCollectionType,
-    DepTableSection, FingerPrintType, LenArrayType, MagicNumberType, PackageSection, RawArrayType,
-    SectionIndex, SectionIndexEntry, SigStructureSection, Size, Type, Uchar, FINGERPRINT_LEN,
-    MAGIC_NUMBER,
+    align_up, is_supported_align, is_supported_version, pad_len, CompressedSection,
+    CrateBinarySection, CrateHeader, CratePackage, CratePackageRef, DataSection,
+    DataSectionCollectionType, DecodeLimits, DepTableEntry, DepTableSection, FeatureTableSection,
+    FingerPrintType, LenArrayType, LockTableSection, MagicNumberType, Off, PackageSection,
+    ProvenanceSection, RawArrayType, SectionIndex, SectionIndexEntry, SigStructureSection, Size,
+    Type, Uchar, UnknownSection, FINGERPRINT_LEN, MAGIC_NUMBER, MAGIC_NUMBER_LEN, NO_ALIGN,
 };
 
 pub const BINCODE_CONFIG: Configuration<LittleEndian, Fixint, NoLimit> = legacy();
@@ -32,6 +42,49 @@ pub fn encode2vec_by_bincode<T: enc::Encode>(val: &T) -> Vec<u8> {
     buffer
 }
 
+///bincode writer feeding sha256, and the inner writer if there is one
+pub struct HashWriter<'a, W: Write> {
+    inner: Option<&'a mut W>,
+    hasher: Sha256,
+    pub bytes_written: usize,
+}
+
+impl<'a, W: Write> HashWriter<'a, W> {
+    pub fn new(inner: Option<&'a mut W>) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            bytes_written: 0,
+        }
+    }
+
+    pub fn finish(self) -> FingerPrintType {
+        self.hasher.finish()
+    }
+}
+
+impl<W: Write> Writer for HashWriter<'_, W> {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), EncodeError> {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.write_all(bytes).map_err(|e| EncodeError::Io {
+                inner: e,
+                index: self.bytes_written,
+            })?;
+        }
+        self.hasher.update(bytes);
+        self.bytes_written += bytes.len();
+        Ok(())
+    }
+}
+
+pub fn encode_to_hash_writer<T: enc::Encode, W: Write>(
+    val: &T,
+    writer: &mut HashWriter<W>,
+) -> Result<(), EncodeError> {
+    let mut encoder = enc::EncoderImpl::new(writer, BINCODE_CONFIG);
+    val.encode(&mut encoder)
+}
+
 pub fn decode_slice_by_bincode<T: bincode::de::Decode>(bin: &[u8]) -> T {
     let (res, _) = bincode::decode_from_slice(bin, BINCODE_CONFIG).unwrap();
     res
@@ -39,7 +92,7 @@ pub fn decode_slice_by_bincode<T: bincode::de::Decode>(bin: &[u8]) -> T {
 
 pub fn create_bincode_slice_decoder(
     bin: &[u8],
-) -> DecoderImpl<SliceReader, Configuration<LittleEndian, Fixint, NoLimit>> {
+) -> DecoderImpl<SliceReader<'_>, Configuration<LittleEndian, Fixint, NoLimit>> {
     DecoderImpl::new(SliceReader::new(bin), BINCODE_CONFIG)
 }
 
@@ -83,12 +136,10 @@ impl<'de, T: BorrowDecode<'de> + 'static> BorrowDecode<'de> for LenArrayType<T>
 //RawArray Encode
 impl<T: Encode + 'static> Encode for RawArrayType<T> {
     fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
-        //TODO FIXME!!!
-        // if TypeId::of::<u8>() == TypeId::of::<T>() || TypeId::of::<Uchar>() == TypeId::of::<T>() || TypeId::of::<Type>() == TypeId::of::<T>(){
-        //     let vec_u8: &[u8] = unsafe { core::mem::transmute(self.arr.as_slice())};
-        //     encoder.writer().write(vec_u8).unwrap();
-        //     return Ok(())
-        // }
+        //byte array is written by one call, u8 is encoded as itself by bincode
+        if let Some(bytes) = (&self.arr as &dyn Any).downcast_ref::<Vec<u8>>() {
+            return encoder.writer().write(bytes);
+        }
         for elem in self.arr.iter() {
             elem.encode(encoder)?
         }
@@ -96,16 +147,33 @@ impl<T: Encode + 'static> Encode for RawArrayType<T> {
     }
 }
 
+///read `elem_num` bytes, borrowing from slice reader if possible,
+///otherwise growing by chunk so that a bogus size fails before a huge allocation
+fn decode_bytes<D: Decoder>(decoder: &mut D, elem_num: usize) -> Result<Vec<u8>, DecodeError> {
+    const CHUNK_SIZE: usize = 64 << 10;
+    decoder.claim_bytes_read(elem_num)?;
+    let reader = decoder.reader();
+    if let Some(bytes) = reader.peek_read(elem_num) {
+        let bytes = bytes.to_vec();
+        reader.consume(elem_num);
+        return Ok(bytes);
+    }
+    let mut bytes = vec![];
+    while bytes.len() < elem_num {
+        let begin = bytes.len();
+        bytes.resize(begin + CHUNK_SIZE.min(elem_num - begin), 0);
+        reader.read(&mut bytes[begin..])?;
+    }
+    Ok(bytes)
+}
+
 ///RawArray Decode
 impl<T: Decode + 'static> RawArrayType<T> {
     pub fn decode<D: Decoder>(decoder: &mut D, elem_num: usize) -> Result<Self, DecodeError> {
-        //TODO FIXME
-        // if TypeId::of::<u8>() == TypeId::of::<T>() || TypeId::of::<Uchar>() == TypeId::of::<T>() || TypeId::of::<Type>() == TypeId::of::<T>(){
-        //     let mut buf = vec![0 as u8; 8 * elem_num];
-        //     decoder.reader().read(buf.as_mut_slice()).unwrap();
-        //     let vec_t: Vec<T> = unsafe{Vec::from_raw_parts(buf.as_ptr() as *mut T, buf.len(), buf.len())};
-        //     return Ok(RawArrayType::from_vec(vec_t));
-        // }
+        if TypeId::of::<T>() == TypeId::of::<u8>() {
+            let bytes: Box<dyn Any> = Box::new(decode_bytes(decoder, elem_num)?);
+            return Ok(RawArrayType::from_vec(*bytes.downcast::<Vec<T>>().unwrap()));
+        }
         let mut raw_array = RawArrayType::<T>::new();
         for _i in 0..elem_num {
             raw_array.arr.push(Decode::decode(decoder)?);
@@ -125,6 +193,23 @@ fn test_raw_array_type() {
     let raw_array = RawArrayType::<i8>::decode(&mut decoder, 3).unwrap();
     // let decode = RawArrayType<T>::
     println!("{:?}", raw_array);
+    assert_eq!(a.arr, raw_array.arr);
+
+    //byte fast path gives the same bytes as encoding element by element
+    let bytes: Vec<u8> = (0..=255).cycle().take(100_000).collect();
+    let mut elem_encoded = vec![];
+    for elem in bytes.iter() {
+        elem_encoded.extend(encode2vec_by_bincode(elem));
+    }
+    let b = RawArrayType::from_vec(bytes.clone());
+    let encode_vec = encode2vec_by_bincode(&b);
+    assert_eq!(elem_encoded, encode_vec);
+    assert_eq!(bytes.len(), b.size());
+    let mut decoder = create_bincode_slice_decoder(encode_vec.as_slice());
+    let raw_array = RawArrayType::<u8>::decode(&mut decoder, bytes.len()).unwrap();
+    assert_eq!(bytes, raw_array.arr);
+    let mut decoder = create_bincode_slice_decoder(encode_vec.as_slice());
+    assert!(RawArrayType::<u8>::decode(&mut decoder, bytes.len() + 1).is_err());
 }
 
 // //PKCS7Struct Encode
@@ -144,28 +229,344 @@ impl Encode for DataSection {
             DataSection::PackageSection(x) => x.encode(encoder)?,
             DataSection::DepTableSection(x) => x.encode(encoder)?,
             DataSection::CrateBinarySection(x) => x.encode(encoder)?,
-            DataSection::SigStructureSection(x) => x.encode(encoder)?, //_ => {panic!("section type error")}
+            DataSection::SigStructureSection(x) => x.encode(encoder)?,
+            DataSection::FeatureTableSection(x) => x.encode(encoder)?,
+            DataSection::LockTableSection(x) => x.encode(encoder)?,
+            DataSection::ProvenanceSection(x) => x.encode(encoder)?,
+            DataSection::UnknownSection(x) => x.bytes.encode(encoder)?,
+            DataSection::CompressedSection(x) => x.bytes.encode(encoder)?,
+        }
+        Ok(())
+    }
+}
+
+//CratePackage Encode, section index starts at a multiple of c_align
+impl Encode for CratePackage {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        Encode::encode(&self.magic_number, encoder)?;
+        Encode::encode(&self.crate_header, encoder)?;
+        Encode::encode(&self.string_table, encoder)?;
+        encode_padding(encoder, self.strtable_pad_len())?;
+        Encode::encode(&self.section_index, encoder)?;
+        Encode::encode(&self.data_sections, encoder)?;
+        Encode::encode(&self.finger_print, encoder)?;
+        Ok(())
+    }
+}
+
+//DataSectionCollectionType Encode, each section starts at a multiple of align
+impl Encode for DataSectionCollectionType {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        let mut offset = 0;
+        for section in self.col.arr.iter() {
+            let pad = pad_len(offset, self.align);
+            encode_padding(encoder, pad)?;
+            let size = encode_size_by_bincode(section);
+            section.encode(encoder)?;
+            offset += pad + size;
+        }
+        Ok(())
+    }
+}
+
+pub fn encode_padding<E: Encoder>(encoder: &mut E, len: usize) -> Result<(), EncodeError> {
+    if len > 0 {
+        encoder.writer().write(&vec![0; len])?;
+    }
+    Ok(())
+}
+
+///padding written by encoder must be zero
+fn decode_padding<D: Decoder>(decoder: &mut D, len: usize) -> Result<(), DecodeError> {
+    let padding = RawArrayType::<Uchar>::decode(decoder, len)?;
+    if padding.arr.iter().any(|b| *b != 0) {
+        return Err(DecodeError::Other("file format not right! - padding"));
+    }
+    Ok(())
+}
+
+//SectionIndex Encode
+impl Encode for SectionIndex {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        let pad = self.entry_size() - SectionIndexEntry::size_by_version(self.c_version);
+        for entry in self.entries.arr.iter() {
+            entry.encode(encoder, self.c_version)?;
+            encode_padding(encoder, pad)?;
+        }
+        Ok(())
+    }
+}
+
+//PackageSection Encode+Decode, layout depends on format version
+impl Encode for PackageSection {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        Encode::encode(&self.pkg_name, encoder)?;
+        Encode::encode(&self.pkg_version, encoder)?;
+        Encode::encode(&self.pkg_license, encoder)?;
+        Encode::encode(&self.pkg_authors, encoder)?;
+        if self.c_version >= 4 {
+            Encode::encode(&self.pkg_description, encoder)?;
+            Encode::encode(&self.pkg_homepage, encoder)?;
+            Encode::encode(&self.pkg_repository, encoder)?;
+            Encode::encode(&self.pkg_documentation, encoder)?;
+            Encode::encode(&self.pkg_readme, encoder)?;
+            Encode::encode(&self.pkg_keywords, encoder)?;
+            Encode::encode(&self.pkg_categories, encoder)?;
+            Encode::encode(&self.pkg_edition, encoder)?;
+            Encode::encode(&self.pkg_rust_version, encoder)?;
+            Encode::encode(&self.pkg_links, encoder)?;
+            Encode::encode(&self.pkg_license_file, encoder)?;
         }
         Ok(())
     }
 }
 
-impl Decode for SigStructureSection {
+impl PackageSection {
+    pub fn decode<D: Decoder>(decoder: &mut D, c_version: Uchar) -> Result<Self, DecodeError> {
+        let mut package_section = PackageSection::new();
+        package_section.c_version = c_version;
+        package_section.pkg_name = Decode::decode(decoder)?;
+        package_section.pkg_version = Decode::decode(decoder)?;
+        package_section.pkg_license = Decode::decode(decoder)?;
+        package_section.pkg_authors = Decode::decode(decoder)?;
+        if c_version >= 4 {
+            package_section.pkg_description = Decode::decode(decoder)?;
+            package_section.pkg_homepage = Decode::decode(decoder)?;
+            package_section.pkg_repository = Decode::decode(decoder)?;
+            package_section.pkg_documentation = Decode::decode(decoder)?;
+            package_section.pkg_readme = Decode::decode(decoder)?;
+            package_section.pkg_keywords = Decode::decode(decoder)?;
+            package_section.pkg_categories = Decode::decode(decoder)?;
+            package_section.pkg_edition = Decode::decode(decoder)?;
+            package_section.pkg_rust_version = Decode::decode(decoder)?;
+            package_section.pkg_links = Decode::decode(decoder)?;
+            package_section.pkg_license_file = Decode::decode(decoder)?;
+        }
+        Ok(package_section)
+    }
+}
+
+//DepTableSection Encode+Decode, layout depends on format version
+impl Encode for DepTableSection {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        Encode::encode(&self.entries.len, encoder)?;
+        for entry in self.entries.arr.iter() {
+            entry.encode(encoder, self.c_version)?;
+        }
+        if self.c_version >= 8 {
+            Encode::encode(&self.dep_unrepresented, encoder)?;
+        } else if self.dep_unrepresented.len != 0 {
+            return Err(EncodeError::Other(
+                "unrepresented dependencies need version 8",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl DepTableSection {
+    pub fn decode<D: Decoder>(decoder: &mut D, c_version: Uchar) -> Result<Self, DecodeError> {
+        let mut dep_table = DepTableSection::new();
+        dep_table.c_version = c_version;
+        dep_table.entries.len = Decode::decode(decoder)?;
+        for _i in 0..dep_table.entries.len {
+            dep_table
+                .entries
+                .arr
+                .push(DepTableEntry::decode(decoder, c_version)?);
+        }
+        if c_version >= 8 {
+            dep_table.dep_unrepresented = Decode::decode(decoder)?;
+        }
+        Ok(dep_table)
+    }
+}
+
+impl DepTableEntry {
+    pub fn encode<E: Encoder>(&self, encoder: &mut E, c_version: Uchar) -> Result<(), EncodeError> {
+        Encode::encode(&self.dep_name, encoder)?;
+        Encode::encode(&self.dep_verreq, encoder)?;
+        Encode::encode(&self.dep_srctype, encoder)?;
+        Encode::encode(&self.dep_srcpath, encoder)?;
+        Encode::encode(&self.dep_platform, encoder)?;
+        if c_version >= 5 {
+            Encode::encode(&self.dep_kind, encoder)?;
+        } else if self.dep_kind != 0 {
+            return Err(EncodeError::Other("dependency kind needs version 5"));
+        }
+        if c_version >= 6 {
+            Encode::encode(&self.dep_optional, encoder)?;
+            Encode::encode(&self.dep_default_features, encoder)?;
+            Encode::encode(&self.dep_features, encoder)?;
+            Encode::encode(&self.dep_package, encoder)?;
+        } else if self.dep_optional != 0
+            || self.dep_default_features != 1
+            || self.dep_features.len != 0
+            || self.dep_package != 0
+        {
+            return Err(EncodeError::Other("dependency attributes need version 6"));
+        }
+        if c_version >= 7 {
+            Encode::encode(&self.dep_srcref, encoder)?;
+        } else if self.dep_srctype > 4 || self.dep_srcref != 0 {
+            return Err(EncodeError::Other("dependency source needs version 7"));
+        }
+        Ok(())
+    }
+
+    pub fn decode<D: Decoder>(decoder: &mut D, c_version: Uchar) -> Result<Self, DecodeError> {
+        let mut entry = DepTableEntry::new();
+        entry.dep_name = Decode::decode(decoder)?;
+        entry.dep_verreq = Decode::decode(decoder)?;
+        entry.dep_srctype = Decode::decode(decoder)?;
+        entry.dep_srcpath = Decode::decode(decoder)?;
+        entry.dep_platform = Decode::decode(decoder)?;
+        if c_version >= 5 {
+            entry.dep_kind = Decode::decode(decoder)?;
+        }
+        if c_version >= 6 {
+            entry.dep_optional = Decode::decode(decoder)?;
+            entry.dep_default_features = Decode::decode(decoder)?;
+            entry.dep_features = Decode::decode(decoder)?;
+            entry.dep_package = Decode::decode(decoder)?;
+        }
+        if c_version >= 7 {
+            entry.dep_srcref = Decode::decode(decoder)?;
+        } else if entry.dep_srctype > 4 {
+            return Err(DecodeError::Other("dependency source needs version 7"));
+        }
+        Ok(entry)
+    }
+}
+
+//SectionIndexEntry Encode+Decode, layout depends on format version
+impl SectionIndexEntry {
+    pub fn encode<E: Encoder>(&self, encoder: &mut E, c_version: Uchar) -> Result<(), EncodeError> {
+        Encode::encode(&self.sh_type, encoder)?;
+        Encode::encode(&self.sh_offset, encoder)?;
+        Encode::encode(&self.sh_size, encoder)?;
+        if c_version >= 1 {
+            Encode::encode(&self.sh_codec, encoder)?;
+        } else if self.sh_codec != CODEC_NONE {
+            return Err(EncodeError::Other("section codec needs version 1"));
+        }
+        if c_version >= 2 {
+            Encode::encode(&self.sh_digest, encoder)?;
+        }
+        Ok(())
+    }
+
+    pub fn decode<D: Decoder>(decoder: &mut D, c_version: Uchar) -> Result<Self, DecodeError> {
+        let sh_type: Type = Decode::decode(decoder)?;
+        let sh_offset: Off = Decode::decode(decoder)?;
+        let sh_size: Size = Decode::decode(decoder)?;
+        let mut entry = SectionIndexEntry::new(sh_type, sh_offset, sh_size);
+        if c_version >= 1 {
+            entry.sh_codec = Decode::decode(decoder)?;
+        }
+        if c_version >= 2 {
+            entry.sh_digest = Decode::decode(decoder)?;
+        }
+        Ok(entry)
+    }
+
+    pub fn size_by_version(c_version: Uchar) -> usize {
+        match c_version {
+            0 => 9,
+            1 => 10,
+            _ => 10 + FINGERPRINT_LEN,
+        }
+    }
+}
+
+//CrateHeader Encode+Decode, layout depends on format version
+impl Encode for CrateHeader {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        Encode::encode(&self.c_version, encoder)?;
+        Encode::encode(&self.strtable_size, encoder)?;
+        Encode::encode(&self.strtable_offset, encoder)?;
+        Encode::encode(&self.si_size, encoder)?;
+        Encode::encode(&self.si_offset, encoder)?;
+        Encode::encode(&self.si_num, encoder)?;
+        Encode::encode(&self.ds_offset, encoder)?;
+        if self.c_version >= 2 {
+            Encode::encode(&self.merkle_root, encoder)?;
+        }
+        if self.c_version >= 3 {
+            Encode::encode(&self.c_align, encoder)?;
+        } else if self.c_align != NO_ALIGN {
+            return Err(EncodeError::Other("section align needs version 3"));
+        }
+        Ok(())
+    }
+}
+
+impl Decode for CrateHeader {
     fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
-        let sigstruct_size: Size = Decode::decode(decoder)?;
-        let sigstruct_type: Type = Decode::decode(decoder)?;
-        let sigstruct_sig = RawArrayType::<u8>::decode(decoder, sigstruct_size as usize)?;
-        //let sigstruct_sig:PKCS7Struct = PKCS7Struct::decode(decoder, sigstruct_size as usize)?;
-        Ok(Self {
-            sigstruct_size,
-            sigstruct_type,
-            sigstruct_sig,
-        })
+        let mut crate_header = CrateHeader::new();
+        crate_header.c_version = Decode::decode(decoder)?;
+        crate_header.strtable_size = Decode::decode(decoder)?;
+        crate_header.strtable_offset = Decode::decode(decoder)?;
+        crate_header.si_size = Decode::decode(decoder)?;
+        crate_header.si_offset = Decode::decode(decoder)?;
+        crate_header.si_num = Decode::decode(decoder)?;
+        crate_header.ds_offset = Decode::decode(decoder)?;
+        if crate_header.c_version >= 2 {
+            crate_header.merkle_root = Decode::decode(decoder)?;
+        }
+        if crate_header.c_version >= 3 {
+            crate_header.c_align = Decode::decode(decoder)?;
+            if !is_supported_align(crate_header.c_align) {
+                return Err(DecodeError::Other("file format not right! - align"));
+            }
+        }
+        Ok(crate_header)
+    }
+}
+
+//SigStructureSection Encode+Decode, layout depends on format version
+impl Encode for SigStructureSection {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        Encode::encode(&self.sigstruct_size, encoder)?;
+        Encode::encode(&self.sigstruct_type, encoder)?;
+        if self.c_version >= 9 {
+            Encode::encode(&self.sigstruct_scheme, encoder)?;
+        } else if self.sigstruct_scheme != 0 {
+            return Err(EncodeError::Other("sig scheme needs version 9"));
+        }
+        Encode::encode(&self.sigstruct_sig, encoder)
+    }
+}
+
+impl SigStructureSection {
+    pub fn decode<D: Decoder>(decoder: &mut D, c_version: Uchar) -> Result<Self, DecodeError> {
+        let mut sig = SigStructureSection::new();
+        sig.c_version = c_version;
+        sig.sigstruct_size = Decode::decode(decoder)?;
+        sig.sigstruct_type = Decode::decode(decoder)?;
+        if c_version >= 9 {
+            sig.sigstruct_scheme = Decode::decode(decoder)?;
+        }
+        sig.sigstruct_sig = RawArrayType::<u8>::decode(decoder, sig.sigstruct_size as usize)?;
+        Ok(sig)
+    }
+}
+
+impl CratePackage {
+    ///decode a signature section fetched on its own, c_version is read from crate header
+    pub fn decode_sig_structure_section(
+        bin: &[u8],
+        c_version: Uchar,
+    ) -> Result<SigStructureSection, CrateSpecError> {
+        Ok(SigStructureSection::decode(
+            &mut create_bincode_slice_decoder(bin),
+            c_version,
+        )?)
     }
 }
 // non-self decode
 
-fn is_magic_number(mn: &MagicNumberType) -> bool {
+fn is_magic_number(mn: &[Uchar]) -> bool {
     for i in 0..MAGIC_NUMBER.len() {
         if mn[i] != MAGIC_NUMBER[i] {
             return false;
@@ -187,135 +588,399 @@ impl CratePackage {
         encode2vec_by_bincode(self)
     }
 
-    pub fn decode_from_slice(bin: &[u8]) -> Result<CratePackage, String> {
-        return match Self::decode(&mut create_bincode_slice_decoder(bin), bin) {
-            Ok(t) => Ok(t),
-            Err(DecodeError::Other(s)) => Err(s.to_string()),
-            Err(_) => Err("file format not right! - others".to_string()),
-        };
+    ///read format version without decoding the file
+    pub fn crate_version(bin: &[u8]) -> Result<Uchar, CrateSpecError> {
+        match bin.get(MAGIC_NUMBER_LEN) {
+            Some(c_version) if is_supported_version(*c_version) => Ok(*c_version),
+            Some(c_version) => Err(CrateSpecError::UnsupportedVersion(*c_version)),
+            None => Err(CrateSpecError::Format(
+                "file format not right! - header".to_string(),
+            )),
+        }
+    }
+
+    ///unsupported version is reported before anything else is decoded
+    fn check_crate_version(bin: &[u8]) -> Result<(), CrateSpecError> {
+        if bin.len() >= MAGIC_NUMBER_LEN && is_magic_number(&bin[..MAGIC_NUMBER_LEN]) {
+            Self::crate_version(bin)?;
+        }
+        Ok(())
+    }
+
+    pub fn decode_from_slice(bin: &[u8]) -> Result<CratePackage, CrateSpecError> {
+        Self::decode_from_slice_with_limits(bin, &DecodeLimits::new())
+    }
+
+    pub fn decode_from_slice_with_limits(
+        bin: &[u8],
+        limits: &DecodeLimits,
+    ) -> Result<CratePackage, CrateSpecError> {
+        Self::check_crate_version(bin)?;
+        let mut decoder = create_bincode_slice_decoder(bin);
+        let (magic_number, c_version) = Self::decode_magic_version(&mut decoder, bin)?;
+        Ok(Self::decode_versioned(
+            &mut decoder,
+            bin,
+            magic_number,
+            c_version,
+            limits,
+        )?)
     }
 
+    ///decode header, string table and section index only,
+    ///bin may end at the beginning of data sections
+    pub fn decode_head_from_slice(bin: &[u8]) -> Result<CratePackage, CrateSpecError> {
+        Self::check_crate_version(bin)?;
+        let mut decoder = create_bincode_slice_decoder(bin);
+        let (magic_number, c_version) = Self::decode_magic_version(&mut decoder, bin)?;
+        Ok(Self::decode_head(
+            &mut decoder,
+            bin,
+            magic_number,
+            c_version,
+            &DecodeLimits::new(),
+        )?)
+    }
+
+    ///dispatch on crate_header.c_version
     pub fn decode<D: Decoder>(decoder: &mut D, bin: &[u8]) -> Result<Self, DecodeError> {
-        let magic_number: MagicNumberType = Decode::decode(decoder).unwrap();
+        let (magic_number, c_version) = Self::decode_magic_version(decoder, bin)?;
+        Self::decode_versioned(decoder, bin, magic_number, c_version, &DecodeLimits::new())
+    }
+
+    fn decode_magic_version<D: Decoder>(
+        decoder: &mut D,
+        bin: &[u8],
+    ) -> Result<(MagicNumberType, Uchar), DecodeError> {
+        let magic_number: MagicNumberType = Decode::decode(decoder)?;
         if !is_magic_number(&magic_number) {
             return Err(DecodeError::Other("magic not right!"));
         }
 
+        match bin.get(MAGIC_NUMBER_LEN) {
+            //later versions only add fields to header and section index entry
+            Some(&c_version) if is_supported_version(c_version) => Ok((magic_number, c_version)),
+            Some(c_version) => Err(DecodeError::OtherString(format!(
+                "unsupported version {}",
+                c_version
+            ))),
+            None => Err(DecodeError::Other("file format not right! - header")),
+        }
+    }
+
+    fn decode_head<D: Decoder>(
+        decoder: &mut D,
+        bin: &[u8],
+        magic_number: MagicNumberType,
+        c_version: Uchar,
+        limits: &DecodeLimits,
+    ) -> Result<Self, DecodeError> {
         let crate_header: CrateHeader = Decode::decode(decoder)?;
+        crate_header.check_limits(limits)?;
 
-        early_return!(
-            bin.len() > (crate_header.strtable_size + crate_header.strtable_offset) as usize,
-            "file format not right! - strtable"
-        );
-        let string_table_bin = &bin[crate_header.strtable_offset as usize
-            ..(crate_header.strtable_size + crate_header.strtable_offset) as usize];
+        let string_table_bin = bin
+            .get(crate_header.strtable_range(bin.len())?)
+            .ok_or(DecodeError::Other("file format not right! - strtable"))?;
         let string_table: RawArrayType<Uchar> = RawArrayType::<Uchar>::decode(
             &mut create_bincode_slice_decoder(string_table_bin),
             string_table_bin.len(),
         )?;
 
-        early_return!(
-            bin.len() > (crate_header.si_offset + crate_header.si_size) as usize,
-            "file format not right! - si"
-        );
-        let section_index_bin = &bin[crate_header.si_offset as usize
-            ..(crate_header.si_offset + crate_header.si_size) as usize];
+        let section_index_bin = bin
+            .get(crate_header.si_range(bin.len())?)
+            .ok_or(DecodeError::Other("file format not right! - si"))?;
         let section_index: SectionIndex = SectionIndex::decode(
             &mut create_bincode_slice_decoder(section_index_bin),
             crate_header.si_num as usize,
+            c_version,
+            crate_header.c_align,
         )?;
 
-        let mut enum_size_off_in_bytes = vec![];
-        section_index.entries.arr.iter().for_each(|index_entry| {
-            enum_size_off_in_bytes.push((
-                index_entry.sh_type as i32,
-                index_entry.sh_size as usize,
-                index_entry.sh_offset as usize,
-            ))
-        });
+        Ok(Self {
+            magic_number,
+            crate_header,
+            string_table,
+            section_index,
+            data_sections: DataSectionCollectionType::new(),
+            finger_print: [0; FINGERPRINT_LEN],
+        })
+    }
 
-        early_return!(
-            bin.len() > crate_header.ds_offset as usize,
-            "file format not right! - ds"
-        );
-        let datasections_bin = &bin[crate_header.ds_offset as usize..];
-        let data_sections = DataSectionCollectionType::decode(
+    fn decode_versioned<D: Decoder>(
+        decoder: &mut D,
+        bin: &[u8],
+        magic_number: MagicNumberType,
+        c_version: Uchar,
+        limits: &DecodeLimits,
+    ) -> Result<Self, DecodeError> {
+        let mut crate_package = Self::decode_head(decoder, bin, magic_number, c_version, limits)?;
+
+        let datasections_bin = bin
+            .get(crate_package.crate_header.ds_range(bin.len())?)
+            .ok_or(DecodeError::Other("file format not right! - ds"))?;
+        crate_package
+            .section_index
+            .check_limits(datasections_bin.len(), limits)?;
+        crate_package.data_sections = DataSectionCollectionType::decode(
             &mut create_bincode_slice_decoder(datasections_bin),
-            enum_size_off_in_bytes,
+            &crate_package.section_index,
+            limits,
         )?;
 
-        early_return!(
-            bin[bin.len() - FINGERPRINT_LEN..].len() == FINGERPRINT_LEN,
-            "file format not right! - fingerprint"
-        );
         let fingerprint_bin = &bin[bin.len() - FINGERPRINT_LEN..];
-        let finger_print: FingerPrintType =
+        crate_package.finger_print =
             Decode::decode(&mut create_bincode_slice_decoder(fingerprint_bin))?;
 
+        Ok(crate_package)
+    }
+}
+
+impl<'a> CratePackageRef<'a> {
+    ///decode header, string table and section index, sections stay in bin
+    pub fn decode_from_slice(bin: &'a [u8]) -> Result<Self, CrateSpecError> {
+        Self::decode_from_slice_with_limits(bin, &DecodeLimits::new())
+    }
+
+    pub fn decode_from_slice_with_limits(
+        bin: &'a [u8],
+        limits: &DecodeLimits,
+    ) -> Result<Self, CrateSpecError> {
+        CratePackage::check_crate_version(bin)?;
+        let mut decoder = create_bincode_slice_decoder(bin);
+        let (_, c_version) = CratePackage::decode_magic_version(&mut decoder, bin)?;
+        let crate_header: CrateHeader = Decode::decode(&mut decoder)?;
+        crate_header.check_limits(limits)?;
+
+        let string_table = bin
+            .get(crate_header.strtable_range(bin.len())?)
+            .ok_or_else(|| {
+                CrateSpecError::Format("file format not right! - strtable".to_string())
+            })?;
+        let section_index_bin = bin
+            .get(crate_header.si_range(bin.len())?)
+            .ok_or_else(|| CrateSpecError::Format("file format not right! - si".to_string()))?;
+        let section_index = SectionIndex::decode(
+            &mut create_bincode_slice_decoder(section_index_bin),
+            crate_header.si_num as usize,
+            c_version,
+            crate_header.c_align,
+        )?;
+        for entry in section_index.entries.arr.iter() {
+            if !is_known_section_type(entry.section_type()) && entry.is_critical() {
+                return Err(CrateSpecError::Format(
+                    "file format not right! - unknown critical section".to_string(),
+                ));
+            }
+        }
+
+        let ds_len = crate_header.ds_range(bin.len())?.len();
+        section_index.check_limits(ds_len, limits)?;
+        let finger_print = &bin[bin.len() - FINGERPRINT_LEN..];
+
         Ok(Self {
-            magic_number,
+            bin,
             crate_header,
             string_table,
             section_index,
-            data_sections,
             finger_print,
+            limits: limits.clone(),
         })
     }
 }
 
+///offset..offset+size if it fits in len, None on overflow
+pub fn checked_range(offset: usize, size: usize, len: usize) -> Option<Range<usize>> {
+    let end = offset.checked_add(size)?;
+    if end > len {
+        return None;
+    }
+    Some(offset..end)
+}
+
+//header fields are untrusted, every range is checked against file length
+impl CrateHeader {
+    pub fn check_limits(&self, limits: &DecodeLimits) -> Result<(), DecodeError> {
+        early_return!(
+            self.strtable_size as usize <= limits.max_strtable_size,
+            "file format not right! - strtable exceeds limit"
+        );
+        early_return!(
+            self.si_num as usize <= limits.max_section_num,
+            "file format not right! - section num exceeds limit"
+        );
+        Ok(())
+    }
+
+    pub fn strtable_range(&self, len: usize) -> Result<Range<usize>, DecodeError> {
+        checked_range(
+            self.strtable_offset as usize,
+            self.strtable_size as usize,
+            len,
+        )
+        .ok_or(DecodeError::Other("file format not right! - strtable"))
+    }
+
+    pub fn si_range(&self, len: usize) -> Result<Range<usize>, DecodeError> {
+        checked_range(self.si_offset as usize, self.si_size as usize, len)
+            .ok_or(DecodeError::Other("file format not right! - si"))
+    }
+
+    ///data sections end where fingerprint begins
+    pub fn ds_range(&self, len: usize) -> Result<Range<usize>, DecodeError> {
+        let ds_end = len
+            .checked_sub(FINGERPRINT_LEN)
+            .ok_or(DecodeError::Other("file format not right! - fingerprint"))?;
+        let ds_offset = self.ds_offset as usize;
+        early_return!(ds_offset <= ds_end, "file format not right! - ds");
+        Ok(ds_offset..ds_end)
+    }
+}
+
+impl SectionIndex {
+    ///every section must lie in data sections, stay under the size limit and be aligned
+    pub fn check_limits(&self, ds_len: usize, limits: &DecodeLimits) -> Result<(), DecodeError> {
+        for entry in self.entries.arr.iter() {
+            early_return!(
+                pad_len(entry.sh_offset as usize, self.align) == 0,
+                "file format not right! - section not aligned"
+            );
+            early_return!(
+                entry.sh_size as usize <= limits.max_crate_size,
+                "file format not right! - section exceeds limit"
+            );
+            early_return!(
+                checked_range(entry.sh_offset as usize, entry.sh_size as usize, ds_len).is_some(),
+                "file format not right! - ds"
+            );
+        }
+        Ok(())
+    }
+}
+
 ///SectionIndex Decode
 impl SectionIndex {
-    pub fn decode<D: Decoder>(decoder: &mut D, elem_num: usize) -> Result<Self, DecodeError> {
-        Ok(Self {
-            entries: RawArrayType::<SectionIndexEntry>::decode(decoder, elem_num)?,
-        })
+    pub fn decode<D: Decoder>(
+        decoder: &mut D,
+        elem_num: usize,
+        c_version: Uchar,
+        align: Uchar,
+    ) -> Result<Self, DecodeError> {
+        let mut section_index = SectionIndex::new();
+        section_index.c_version = c_version;
+        section_index.align = align;
+        let pad = section_index.entry_size() - SectionIndexEntry::size_by_version(c_version);
+        for _i in 0..elem_num {
+            section_index
+                .entries
+                .arr
+                .push(SectionIndexEntry::decode(decoder, c_version)?);
+            decode_padding(decoder, pad)?;
+        }
+        Ok(section_index)
     }
 }
 
+pub fn is_known_section_type(typ: Type) -> bool {
+    matches!(typ, 0 | 1 | 3 | 4 | 5 | 6 | 7)
+}
+
+///decode a known section whose payload is not encoded by codec
+fn decode_data_section<D: Decoder>(
+    decoder: &mut D,
+    typ: Type,
+    size: usize,
+    c_version: Uchar,
+) -> Result<DataSection, DecodeError> {
+    Ok(match typ {
+        0 => {
+            let pack_sec: PackageSection = PackageSection::decode(decoder, c_version)?;
+            DataSection::PackageSection(pack_sec)
+        }
+        1 => {
+            let dep_table: DepTableSection = DepTableSection::decode(decoder, c_version)?;
+            DataSection::DepTableSection(dep_table)
+        }
+        3 => {
+            let crate_binary: CrateBinarySection = CrateBinarySection::decode(decoder, size)?;
+            DataSection::CrateBinarySection(crate_binary)
+        }
+        4 => {
+            let sig_structure = SigStructureSection::decode(decoder, c_version)?;
+            DataSection::SigStructureSection(sig_structure)
+        }
+        5 => {
+            let feature_table: FeatureTableSection = Decode::decode(decoder)?;
+            DataSection::FeatureTableSection(feature_table)
+        }
+        6 => {
+            let lock_table: LockTableSection = Decode::decode(decoder)?;
+            DataSection::LockTableSection(lock_table)
+        }
+        7 => {
+            let provenance: ProvenanceSection = Decode::decode(decoder)?;
+            DataSection::ProvenanceSection(provenance)
+        }
+        _ => return Err(DecodeError::Other("file format not right!")),
+    })
+}
+
 ///RawCollection Decode
 impl DataSectionCollectionType {
+    ///section_index must have passed SectionIndex::check_limits
     pub fn decode<D: Decoder>(
         decoder: &mut D,
-        enum_size_offset_in_bytes: Vec<(i32, usize, usize)>,
+        section_index: &SectionIndex,
+        limits: &DecodeLimits,
     ) -> Result<Self, DecodeError> {
         let mut raw_col = DataSectionCollectionType::new();
+        raw_col.align = section_index.align;
         let mut consume_size = 0;
-        for (type_id, size, offset) in enum_size_offset_in_bytes.into_iter() {
+        for entry in section_index.entries.arr.iter() {
+            let size = entry.sh_size as usize;
+            let offset = entry.sh_offset as usize;
             if consume_size > offset {
                 return Err(DecodeError::Other("file format not right!"));
             }
             if consume_size < offset {
-                decoder.reader().consume(offset - consume_size);
+                //gap is zero padding since version 3, older writers never left a gap
+                if section_index.c_version >= 3 {
+                    decode_padding(decoder, offset - consume_size)?;
+                } else {
+                    decoder.reader().consume(offset - consume_size);
+                }
                 consume_size = offset;
             }
-            match type_id {
-                0 => {
-                    let pack_sec: PackageSection = Decode::decode(decoder)?;
-                    raw_col.col.arr.push(DataSection::PackageSection(pack_sec));
-                }
-                1 => {
-                    let dep_table: DepTableSection = Decode::decode(decoder)?;
-                    raw_col
-                        .col
-                        .arr
-                        .push(DataSection::DepTableSection(dep_table));
+            let typ = entry.section_type();
+            if !is_known_section_type(typ) {
+                if entry.is_critical() {
+                    return Err(DecodeError::Other(
+                        "file format not right! - unknown critical section",
+                    ));
                 }
-                3 => {
-                    let crate_binary: CrateBinarySection =
-                        CrateBinarySection::decode(decoder, size)?;
-                    raw_col
-                        .col
-                        .arr
-                        .push(DataSection::CrateBinarySection(crate_binary));
-                }
-                4 => {
-                    let sig_structure: SigStructureSection = Decode::decode(decoder)?;
-                    raw_col
-                        .col
-                        .arr
-                        .push(DataSection::SigStructureSection(sig_structure));
-                }
-                _ => return Err(DecodeError::Other("file format not right!")),
+                let unknown: UnknownSection =
+                    UnknownSection::decode(decoder, entry.sh_type, entry.sh_codec, size)?;
+                raw_col.col.arr.push(DataSection::UnknownSection(unknown));
+            } else if entry.sh_codec == CODEC_NONE {
+                raw_col.col.arr.push(decode_data_section(
+                    decoder,
+                    typ,
+                    size,
+                    section_index.c_version,
+                )?);
+            } else {
+                let encoded = RawArrayType::<Uchar>::decode(decoder, size)?;
+                let decoded = decode_by_codec(
+                    entry.sh_codec,
+                    encoded.arr.as_slice(),
+                    limits.max_crate_size,
+                )
+                .map_err(|e| DecodeError::OtherString(e.to_string()))?;
+                raw_col.col.arr.push(decode_data_section(
+                    &mut create_bincode_slice_decoder(decoded.as_slice()),
+                    typ,
+                    decoded.len(),
+                    section_index.c_version,
+                )?);
             }
             consume_size += size;
         }
@@ -327,6 +992,7 @@ impl DataSectionCollectionType {
         let mut offset: usize = 0;
         self.col.arr.iter().for_each(|x| {
             let size = encode_size_by_bincode(x);
+            offset = align_up(offset, self.align);
             v.push((size, offset));
             offset += size;
         });
@@ -349,6 +1015,20 @@ impl CrateBinarySection {
     }
 }
 
+//UnknownSection decode
+impl UnknownSection {
+    pub fn decode<D: Decoder>(
+        decoder: &mut D,
+        typ: Type,
+        codec: Uchar,
+        size_in_bytes: usize,
+    ) -> Result<Self, DecodeError> {
+        let mut unknown = UnknownSection::new(typ, codec);
+        unknown.bytes = RawArrayType::<Uchar>::decode(decoder, size_in_bytes)?;
+        Ok(unknown)
+    }
+}
+
 //PKCS7Struct decode
 // impl PKCS7Struct{
 //     fn decode<D: Decoder>(decoder: &mut D, size_in_bytes:usize) -> Result<Self, DecodeError> {
@@ -368,6 +1048,15 @@ impl CrateHeader {
     }
 }
 
+impl CratePackage {
+    ///zero bytes between string table and section index
+    pub fn strtable_pad_len(&self) -> usize {
+        let strtable_end =
+            self.magic_number.len() + self.crate_header.size() + self.string_table.size();
+        pad_len(strtable_end, self.crate_header.c_align)
+    }
+}
+
 impl<T: Encode + 'static> RawArrayType<T> {
     pub fn size(&self) -> usize {
         encode_size_by_bincode(self)
@@ -383,20 +1072,22 @@ impl SectionIndex {
         self.entries.arr.len()
     }
 
+    ///entry size including padding
+    pub fn entry_size(&self) -> usize {
+        align_up(
+            SectionIndexEntry::size_by_version(self.c_version),
+            self.align,
+        )
+    }
+
     pub fn none_sig_size(&self) -> usize {
-        let mut total_len = 0;
-        self.entries.arr.iter().for_each(|x| {
-            if x.sh_type != 4 {
-                total_len += x.size();
-            }
-        });
-        total_len
+        self.none_sig_num() * self.entry_size()
     }
 
     pub fn none_sig_num(&self) -> usize {
         let mut total_len = 0;
         self.entries.arr.iter().for_each(|x| {
-            if x.sh_type != 4 {
+            if x.section_type() != 4 {
                 total_len += 1;
             }
         });
@@ -418,9 +1109,16 @@ impl SectionIndex {
         buf
     }
 
-    pub fn datasection_size_without_sig(&self) -> usize {
-        (self.entries.arr[self.none_sig_num() - 1].sh_offset
-            + self.entries.arr[self.none_sig_num() - 1].sh_size) as usize
+    pub fn datasection_size_without_sig(&self) -> Result<usize, CrateSpecError> {
+        let si_err = || CrateSpecError::Format("file format not right! - si".to_string());
+        let none_sig_num = self.none_sig_num();
+        if none_sig_num == 0 {
+            return Err(si_err());
+        }
+        let last = &self.entries.arr[none_sig_num - 1];
+        (last.sh_offset as usize)
+            .checked_add(last.sh_size as usize)
+            .ok_or_else(si_err)
     }
 }
 
@@ -442,6 +1140,24 @@ impl DepTableSection {
     }
 }
 
+impl FeatureTableSection {
+    pub fn size(&self) -> usize {
+        encode_size_by_bincode(self)
+    }
+}
+
+impl LockTableSection {
+    pub fn size(&self) -> usize {
+        encode_size_by_bincode(self)
+    }
+}
+
+impl ProvenanceSection {
+    pub fn size(&self) -> usize {
+        encode_size_by_bincode(self)
+    }
+}
+
 impl CrateBinarySection {
     pub fn size(&self) -> usize {
         encode_size_by_bincode(self)
@@ -454,8 +1170,31 @@ impl SigStructureSection {
     }
 }
 
-impl SectionIndexEntry {
+impl UnknownSection {
     pub fn size(&self) -> usize {
-        encode_size_by_bincode(self)
+        self.bytes.size()
     }
 }
+
+impl CompressedSection {
+    pub fn size(&self) -> usize {
+        self.bytes.size()
+    }
+}
+
+#[test]
+fn test_dep_srctype_version() {
+    let mut dep_table = DepTableSection::new();
+    dep_table.c_version = 6;
+    let mut entry = DepTableEntry::new();
+    entry.dep_srctype = 1;
+    dep_table.entries = LenArrayType::from_vec(vec![entry]);
+    let mut bin = encode2vec_by_bincode(&dep_table);
+    let mut decoder = create_bincode_slice_decoder(bin.as_slice());
+    assert!(DepTableSection::decode(&mut decoder, 6).is_ok());
+
+    //path and pinned git sources have no dep_srcref before version 7
+    bin[12] = 5;
+    let mut decoder = create_bincode_slice_decoder(bin.as_slice());
+    assert!(DepTableSection::decode(&mut decoder, 6).is_err());
+}
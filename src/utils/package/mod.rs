@@ -53,7 +53,7 @@ impl<T> Default for LenArrayType<T> {
 }
 
 impl<T: Clone> LenArrayType<T> {
-    pub fn copy_from_vec(v: &Vec<T>) -> Self {
+    pub fn copy_from_vec(v: &[T]) -> Self {
         let mut len_array = Self::new();
         len_array.arr = v.to_vec();
         len_array.len = v.len() as Size;
@@ -89,18 +89,21 @@ impl<T> Default for RawArrayType<T> {
     }
 }
 
-/// auto encode
+/// custom encode
 /// self decode
 /// collections(array whose elem is enum)
-#[derive(Encode, Debug)]
+#[derive(Debug)]
 pub struct DataSectionCollectionType {
     pub col: RawArrayType<DataSection>,
+    ///alignment of section offsets, not encoded
+    pub align: Uchar,
 }
 
 impl DataSectionCollectionType {
     pub fn new() -> Self {
         Self {
             col: RawArrayType::new(),
+            align: NO_ALIGN,
         }
     }
 }
@@ -135,14 +138,80 @@ pub const FINGERPRINT_LEN: usize = 32;
 
 pub type FingerPrintType = [Uchar; FINGERPRINT_LEN];
 
-pub const CRATEVERSION: Uchar = 0;
+///format version written by default
+///version 1 adds sh_codec to section index entry
+///version 2 adds sh_digest to section index entry and merkle_root to crate header
+///version 3 adds c_align to crate header
+///version 4 adds extended package metadata to package section
+///version 5 adds dep_kind to dependency table entry
+///version 6 adds optional, default-features, features and package to dependency table entry
+///version 7 adds path and pinned git dep_srctype and dep_srcref to dependency table entry
+///version 8 adds dep_unrepresented to dependency table section
+///version 9 adds sigstruct_scheme to signature section
+pub const CRATEVERSION: Uchar = 9;
+
+///oldest format version this crate can read and write
+pub const MIN_CRATEVERSION: Uchar = 0;
+
+pub fn is_supported_version(c_version: Uchar) -> bool {
+    (MIN_CRATEVERSION..=CRATEVERSION).contains(&c_version)
+}
+
+///sections and section index entries are packed
+pub const NO_ALIGN: Uchar = 1;
+
+pub fn is_supported_align(align: Uchar) -> bool {
+    matches!(align, 1 | 4 | 8)
+}
+
+///zero bytes to write at offset so that the next item starts at a multiple of align
+pub fn pad_len(offset: usize, align: Uchar) -> usize {
+    let align = align as usize;
+    (align - offset % align) % align
+}
+
+pub fn align_up(offset: usize, align: Uchar) -> usize {
+    offset + pad_len(offset, align)
+}
+
+///high bit of sh_type, reader must reject the file if it does not know a critical section
+pub const SECTION_CRITICAL_FLAG: Type = 0x80;
+
+pub const DEFAULT_MAX_SECTION_NUM: usize = 1024;
+pub const DEFAULT_MAX_STRTABLE_SIZE: usize = 16 << 20;
+pub const DEFAULT_MAX_CRATE_SIZE: usize = 512 << 20;
+
+///limits checked while decoding untrusted files
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodeLimits {
+    pub max_section_num: usize,
+    pub max_strtable_size: usize,
+    ///bounds crate binary, every other section and every payload decoded by codec
+    pub max_crate_size: usize,
+}
+
+impl DecodeLimits {
+    pub fn new() -> Self {
+        Self {
+            max_section_num: DEFAULT_MAX_SECTION_NUM,
+            max_strtable_size: DEFAULT_MAX_STRTABLE_SIZE,
+            max_crate_size: DEFAULT_MAX_CRATE_SIZE,
+        }
+    }
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 //package structure
 
-//auto encode
+//custom encode
 //non-self decode
 ///top-level package structure
-#[derive(Encode, Debug)]
+#[derive(Debug)]
 pub struct CratePackage {
     pub magic_number: MagicNumberType,
     pub crate_header: CrateHeader,
@@ -171,10 +240,23 @@ impl Default for CratePackage {
     }
 }
 
-//auto encode
-//auto decode
+//non-self decode
+///package structure borrowing file bytes, for example a memory-mapped file,
+///sections are given back as slices
+#[derive(Debug)]
+pub struct CratePackageRef<'a> {
+    pub bin: &'a [u8],
+    pub crate_header: CrateHeader,
+    pub string_table: &'a [u8],
+    pub section_index: SectionIndex,
+    pub finger_print: &'a [u8],
+    pub limits: DecodeLimits,
+}
+
+//custom encode
+//custom decode
 ///crate header structure
-#[derive(Encode, Decode, Debug)]
+#[derive(Debug)]
 pub struct CrateHeader {
     pub c_version: Uchar,
     pub strtable_size: Size,
@@ -185,12 +267,16 @@ pub struct CrateHeader {
     // pub si_not_sig_num: Size,
     // pub si_not_sig_size: Size,
     pub ds_offset: Off,
+    ///merkle root over version, align, string table and none-sig index entries, since version 2
+    pub merkle_root: FingerPrintType,
+    ///section index and every section start at a multiple of c_align, since version 3
+    pub c_align: Uchar,
 }
 
 impl CrateHeader {
     pub fn new() -> Self {
         Self {
-            c_version: Default::default(),
+            c_version: CRATEVERSION,
             strtable_size: Default::default(),
             strtable_offset: Default::default(),
             si_num: Default::default(),
@@ -199,6 +285,8 @@ impl CrateHeader {
             // si_not_sig_num: Default::default(),
             si_offset: Default::default(),
             ds_offset: Default::default(),
+            merkle_root: [0; FINGERPRINT_LEN],
+            c_align: NO_ALIGN,
         }
     }
 }
@@ -209,18 +297,24 @@ impl Default for CrateHeader {
     }
 }
 
-//auto encode
+//custom encode
 //self decode
 ///section index structure
-#[derive(Encode, Debug)]
+#[derive(Debug)]
 pub struct SectionIndex {
     pub entries: RawArrayType<SectionIndexEntry>,
+    ///format version deciding the entry layout, not encoded
+    pub c_version: Uchar,
+    ///each entry is padded to a multiple of align, not encoded
+    pub align: Uchar,
 }
 
 impl SectionIndex {
     pub fn new() -> Self {
         Self {
             entries: RawArrayType::new(),
+            c_version: CRATEVERSION,
+            align: NO_ALIGN,
         }
     }
 
@@ -235,20 +329,24 @@ impl Default for SectionIndex {
     }
 }
 
-//auto encode
-//auto decode
+//custom encode
+//custom decode
 ///section index entry structure
-#[derive(Encode, Decode, Debug, Default)]
+#[derive(Debug, Default)]
 pub struct SectionIndexEntry {
     /*
-    FIXME In RFC0.1 there are no alignment requirements for the struct.
-    SectionIndexEntry's size is 9 bytes, 9 is not a multiple of 4
-    we may padding 3 bytes of 0 after each SectionIndexEntry,
+    In RFC0.1 there are no alignment requirements for the struct.
+    SectionIndexEntry's size is 9 bytes, 9 is not a multiple of 4,
+    since version 3 each SectionIndexEntry is padded with 0 to a multiple of c_align,
     so here, we use a user-defined encoder to implement the serialization of SectionIndex.
       */
     pub sh_type: Type,
     pub sh_offset: Off,
     pub sh_size: Size,
+    ///codec of section payload, since version 1
+    pub sh_codec: Uchar,
+    ///sha256 of section bytes in file, since version 2
+    pub sh_digest: FingerPrintType,
 }
 
 impl SectionIndexEntry {
@@ -257,8 +355,19 @@ impl SectionIndexEntry {
             sh_type,
             sh_offset,
             sh_size,
+            sh_codec: 0,
+            sh_digest: [0; FINGERPRINT_LEN],
         }
     }
+
+    ///section type without critical flag
+    pub fn section_type(&self) -> Type {
+        self.sh_type & !SECTION_CRITICAL_FLAG
+    }
+
+    pub fn is_critical(&self) -> bool {
+        self.sh_type & SECTION_CRITICAL_FLAG != 0
+    }
 }
 
 //custom encode
@@ -274,6 +383,16 @@ pub enum DataSection {
     CrateBinarySection(CrateBinarySection),
     //4
     SigStructureSection(SigStructureSection),
+    //5
+    FeatureTableSection(FeatureTableSection),
+    //6
+    LockTableSection(LockTableSection),
+    //7
+    ProvenanceSection(ProvenanceSection),
+    //section written by a newer writer, kept as it is
+    UnknownSection(UnknownSection),
+    //section payload encoded by sh_codec
+    CompressedSection(CompressedSection),
 }
 
 pub fn datasection_type(d: &DataSection) -> Type {
@@ -282,18 +401,60 @@ pub fn datasection_type(d: &DataSection) -> Type {
         DataSection::DepTableSection(_) => 1,
         DataSection::CrateBinarySection(_) => 3,
         DataSection::SigStructureSection(_) => 4,
+        DataSection::FeatureTableSection(_) => 5,
+        DataSection::LockTableSection(_) => 6,
+        DataSection::ProvenanceSection(_) => 7,
+        DataSection::UnknownSection(x) => x.typ,
+        DataSection::CompressedSection(x) => x.typ,
     }
 }
 
-//auto encode
-//auto decode
+pub fn datasection_codec(d: &DataSection) -> Uchar {
+    match d {
+        DataSection::UnknownSection(x) => x.codec,
+        DataSection::CompressedSection(x) => x.codec,
+        _ => 0,
+    }
+}
+
+///whether old readers must understand the section to read the file
+pub fn datasection_critical(d: &DataSection) -> bool {
+    match d {
+        DataSection::PackageSection(_)
+        | DataSection::DepTableSection(_)
+        | DataSection::CrateBinarySection(_)
+        | DataSection::SigStructureSection(_)
+        | DataSection::FeatureTableSection(_)
+        | DataSection::LockTableSection(_)
+        | DataSection::ProvenanceSection(_) => false,
+        DataSection::UnknownSection(x) => x.typ & SECTION_CRITICAL_FLAG != 0,
+        DataSection::CompressedSection(x) => x.typ & SECTION_CRITICAL_FLAG != 0,
+    }
+}
+
+//custom encode
+//custom decode
 ///package section structure
-#[derive(Encode, Decode, Debug)]
+#[derive(Debug)]
 pub struct PackageSection {
     pub pkg_name: StrOff,
     pub pkg_version: StrOff,
     pub pkg_license: StrOff,
     pub pkg_authors: LenArrayType<StrOff>,
+    ///extended metadata, since version 4
+    pub pkg_description: StrOff,
+    pub pkg_homepage: StrOff,
+    pub pkg_repository: StrOff,
+    pub pkg_documentation: StrOff,
+    pub pkg_readme: StrOff,
+    pub pkg_keywords: LenArrayType<StrOff>,
+    pub pkg_categories: LenArrayType<StrOff>,
+    pub pkg_edition: StrOff,
+    pub pkg_rust_version: StrOff,
+    pub pkg_links: StrOff,
+    pub pkg_license_file: StrOff,
+    ///format version deciding the section layout, not encoded
+    pub c_version: Uchar,
 }
 
 impl PackageSection {
@@ -303,6 +464,18 @@ impl PackageSection {
             pkg_version: 0,
             pkg_license: 0,
             pkg_authors: LenArrayType::new(),
+            pkg_description: 0,
+            pkg_homepage: 0,
+            pkg_repository: 0,
+            pkg_documentation: 0,
+            pkg_readme: 0,
+            pkg_keywords: LenArrayType::new(),
+            pkg_categories: LenArrayType::new(),
+            pkg_edition: 0,
+            pkg_rust_version: 0,
+            pkg_links: 0,
+            pkg_license_file: 0,
+            c_version: CRATEVERSION,
         }
     }
 }
@@ -313,16 +486,28 @@ impl Default for PackageSection {
     }
 }
 
-//auto encode
-//auto decode
+//custom encode
+//custom decode
 ///Dependency table entry structure
-#[derive(Encode, Decode, Debug)]
+#[derive(Debug)]
 pub struct DepTableEntry {
     pub dep_name: StrOff,
     pub dep_verreq: StrOff,
     pub dep_srctype: Type,
     pub dep_srcpath: StrOff,
     pub dep_platform: StrOff,
+    ///0 normal, 1 dev, 2 build, since version 5
+    pub dep_kind: Type,
+    ///1 if optional, since version 6
+    pub dep_optional: Uchar,
+    ///0 if default-features = false, since version 6
+    pub dep_default_features: Uchar,
+    ///features enabled on the dependency, since version 6
+    pub dep_features: LenArrayType<StrOff>,
+    ///real package name of a renamed dependency, empty if not renamed, since version 6
+    pub dep_package: StrOff,
+    ///git rev, branch or tag of dep_srctype 6, 7 or 8, since version 7
+    pub dep_srcref: StrOff,
 }
 
 impl DepTableEntry {
@@ -333,6 +518,12 @@ impl DepTableEntry {
             dep_srctype: 0,
             dep_srcpath: 0,
             dep_platform: 0,
+            dep_kind: 0,
+            dep_optional: 0,
+            dep_default_features: 1,
+            dep_features: LenArrayType::new(),
+            dep_package: 0,
+            dep_srcref: 0,
         }
     }
 }
@@ -343,18 +534,24 @@ impl Default for DepTableEntry {
     }
 }
 
-//auto encode
+//custom encode
 //non-self decode
 ///Dependency table section structure
-#[derive(Encode, Decode, Debug)]
+#[derive(Debug)]
 pub struct DepTableSection {
     pub entries: LenArrayType<DepTableEntry>,
+    ///names of dependencies Cargo.toml declares but entries can not represent, since version 8
+    pub dep_unrepresented: LenArrayType<StrOff>,
+    ///format version deciding the entry layout, not encoded
+    pub c_version: Uchar,
 }
 
 impl DepTableSection {
     pub fn new() -> Self {
         Self {
             entries: LenArrayType::new(),
+            dep_unrepresented: LenArrayType::new(),
+            c_version: CRATEVERSION,
         }
     }
 }
@@ -365,6 +562,141 @@ impl Default for DepTableSection {
     }
 }
 
+//auto encode
+//auto decode
+///Feature table entry structure, activations are kept as written in [features]
+#[derive(Encode, Decode, Debug)]
+pub struct FeatureTableEntry {
+    pub feat_name: StrOff,
+    pub feat_activations: LenArrayType<StrOff>,
+}
+
+impl FeatureTableEntry {
+    pub fn new() -> Self {
+        Self {
+            feat_name: 0,
+            feat_activations: LenArrayType::new(),
+        }
+    }
+}
+
+impl Default for FeatureTableEntry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//auto encode
+//auto decode
+///Feature table section structure
+#[derive(Encode, Decode, Debug)]
+pub struct FeatureTableSection {
+    pub entries: LenArrayType<FeatureTableEntry>,
+}
+
+impl FeatureTableSection {
+    pub fn new() -> Self {
+        Self {
+            entries: LenArrayType::new(),
+        }
+    }
+}
+
+impl Default for FeatureTableSection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//auto encode
+//auto decode
+///Lock table entry structure, one [[package]] of Cargo.lock
+#[derive(Encode, Decode, Debug)]
+pub struct LockTableEntry {
+    pub lock_name: StrOff,
+    pub lock_version: StrOff,
+    pub lock_source: StrOff,
+    pub lock_checksum: StrOff,
+    pub lock_deps: LenArrayType<StrOff>,
+}
+
+impl LockTableEntry {
+    pub fn new() -> Self {
+        Self {
+            lock_name: 0,
+            lock_version: 0,
+            lock_source: 0,
+            lock_checksum: 0,
+            lock_deps: LenArrayType::new(),
+        }
+    }
+}
+
+impl Default for LockTableEntry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//auto encode
+//auto decode
+///Lock table section structure, the resolved dependency graph
+#[derive(Encode, Decode, Debug)]
+pub struct LockTableSection {
+    pub entries: LenArrayType<LockTableEntry>,
+}
+
+impl LockTableSection {
+    pub fn new() -> Self {
+        Self {
+            entries: LenArrayType::new(),
+        }
+    }
+}
+
+impl Default for LockTableSection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//auto encode
+//auto decode
+///Provenance section structure, the environment the package was packed in
+#[derive(Encode, Decode, Debug)]
+pub struct ProvenanceSection {
+    pub prov_rustc: StrOff,
+    pub prov_cargo: StrOff,
+    pub prov_host: StrOff,
+    ///seconds since unix epoch
+    pub prov_timestamp: u64,
+    pub prov_crate_spec: StrOff,
+    pub prov_vcs_commit: StrOff,
+    pub prov_vcs_dirty: Uchar,
+    pub prov_vcs_path: StrOff,
+}
+
+impl ProvenanceSection {
+    pub fn new() -> Self {
+        Self {
+            prov_rustc: 0,
+            prov_cargo: 0,
+            prov_host: 0,
+            prov_timestamp: 0,
+            prov_crate_spec: 0,
+            prov_vcs_commit: 0,
+            prov_vcs_dirty: 0,
+            prov_vcs_path: 0,
+        }
+    }
+}
+
+impl Default for ProvenanceSection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 //auto encode
 //non-self decode
 #[derive(Encode, Debug)]
@@ -386,14 +718,18 @@ impl Default for CrateBinarySection {
     }
 }
 
-//auto encode
-//custom decode
+//custom encode
+//non-self decode
 ///Signature  section structure
-#[derive(Encode, Debug)]
+#[derive(Debug)]
 pub struct SigStructureSection {
     pub sigstruct_size: Size,
     pub sigstruct_type: Type,
+    ///0 S/MIME pkcs7, 1 raw ed25519, 2 raw ecdsa p-256, 3 DER cms, since version 9
+    pub sigstruct_scheme: Uchar,
     pub sigstruct_sig: RawArrayType<u8>,
+    ///format version deciding the layout, not encoded
+    pub c_version: Uchar,
 }
 
 impl SigStructureSection {
@@ -401,7 +737,9 @@ impl SigStructureSection {
         Self {
             sigstruct_size: 0,
             sigstruct_type: 0,
+            sigstruct_scheme: 0,
             sigstruct_sig: RawArrayType::new(),
+            c_version: CRATEVERSION,
         }
     }
 }
@@ -411,3 +749,47 @@ impl Default for SigStructureSection {
         Self::new()
     }
 }
+
+//custom encode
+//non-self decode
+///section whose type is unknown to this reader
+#[derive(Debug)]
+pub struct UnknownSection {
+    ///raw sh_type in section index
+    pub typ: Type,
+    ///raw sh_codec in section index, bytes are kept encoded
+    pub codec: Uchar,
+    pub bytes: RawArrayType<Uchar>,
+}
+
+impl UnknownSection {
+    pub fn new(typ: Type, codec: Uchar) -> Self {
+        Self {
+            typ,
+            codec,
+            bytes: RawArrayType::new(),
+        }
+    }
+}
+
+//custom encode
+//non-self decode
+///known section whose payload is encoded by a codec before it is written,
+///decoder always gives back the section itself
+#[derive(Debug)]
+pub struct CompressedSection {
+    ///raw sh_type in section index
+    pub typ: Type,
+    pub codec: Uchar,
+    pub bytes: RawArrayType<Uchar>,
+}
+
+impl CompressedSection {
+    pub fn new(typ: Type, codec: Uchar) -> Self {
+        Self {
+            typ,
+            codec,
+            bytes: RawArrayType::new(),
+        }
+    }
+}
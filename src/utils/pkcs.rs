@@ -1,20 +1,83 @@
+use openssl::asn1::Asn1Time;
+use openssl::bn::BigNum;
+use openssl::ecdsa::EcdsaSig;
 use openssl::hash::{hash, MessageDigest};
+use openssl::nid::Nid;
+use openssl::sign::{Signer, Verifier};
 use std::fmt::{Debug, Formatter};
 use std::fs;
 use std::path::Path;
 
+use crate::utils::error::CrateSpecError;
+use crate::utils::package::Uchar;
+use openssl::cms::{CMSOptions, CmsContentInfo};
 use openssl::pkcs7::Pkcs7;
 use openssl::pkcs7::Pkcs7Flags;
-use openssl::pkey::PKey;
+use openssl::pkey::{HasPublic, Id, PKey, PKeyRef};
 use openssl::stack::Stack;
-use openssl::x509::store::X509StoreBuilder;
-use openssl::x509::X509;
+use openssl::x509::store::{X509Store, X509StoreBuilder};
+use openssl::x509::{X509NameRef, X509};
+
+///DER content of OID 1.2.840.113549.1.9.5, pkcs9 signingTime
+const SIGNING_TIME_OID: [u8; 9] = [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x05];
+
+pub const SIG_SCHEME_PKCS7: Uchar = 0;
+pub const SIG_SCHEME_ED25519: Uchar = 1;
+pub const SIG_SCHEME_P256: Uchar = 2;
+pub const SIG_SCHEME_CMS: Uchar = 3;
+
+///sha256 of the DER public key, prefixed to a raw signature
+const RAW_KEY_ID_LEN: usize = 32;
+///ed25519 signature, or r and s of ecdsa p-256 padded to 32 bytes each
+const RAW_SIG_LEN: usize = 64;
+
+///how a signature is made, since version 9
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SIGSCHEME {
+    ///pkcs7 S/MIME verified against root-ca
+    PKCS7,
+    ///DER cms SignedData verified against root-ca, written as PKCS7 before version 9
+    CMS,
+    ///raw signature verified against a keyring
+    ED25519,
+    ///raw signature verified against a keyring
+    P256,
+}
+
+impl SIGSCHEME {
+    pub fn id(&self) -> Uchar {
+        match self {
+            SIGSCHEME::PKCS7 => SIG_SCHEME_PKCS7,
+            SIGSCHEME::CMS => SIG_SCHEME_CMS,
+            SIGSCHEME::ED25519 => SIG_SCHEME_ED25519,
+            SIGSCHEME::P256 => SIG_SCHEME_P256,
+        }
+    }
+}
+
+///who signed, read from the signer certificate of a verified signature
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct SignerInfo {
+    pub subject: String,
+    pub issuer: String,
+    ///hex
+    pub serial: String,
+    ///hex of sha256 over the DER certificate, or over the DER public key of a raw signature
+    pub fingerprint: String,
+    pub not_before: String,
+    pub not_after: String,
+    ///signingTime signed attribute, empty if the signature has none
+    pub signing_time: String,
+}
 
 #[derive(PartialEq)]
 pub struct PKCS {
     cert_bin: Vec<u8>,
     pkey_bin: Vec<u8>,
     root_ca_bins: Vec<Vec<u8>>,
+    ///pem files of intermediate certificates, embedded in the signature
+    chain_bins: Vec<Vec<u8>>,
+    scheme: Uchar,
 }
 
 impl Debug for PKCS {
@@ -23,20 +86,176 @@ impl Debug for PKCS {
     }
 }
 
+fn read_file(path: &str) -> Result<Vec<u8>, CrateSpecError> {
+    fs::read(Path::new(path)).map_err(CrateSpecError::from)
+}
+
+fn root_ca_store(root_ca_bins: &[Vec<u8>]) -> Result<X509Store, CrateSpecError> {
+    let mut store_builder =
+        X509StoreBuilder::new().map_err(|e| CrateSpecError::Trust(e.to_string()))?;
+    for root_ca_bin in root_ca_bins.iter() {
+        let root_ca = X509::from_pem(root_ca_bin.as_slice())
+            .map_err(|e| CrateSpecError::Trust(format!("root-ca not right - {}", e)))?;
+        store_builder
+            .add_cert(root_ca)
+            .map_err(|e| CrateSpecError::Trust(e.to_string()))?;
+    }
+    Ok(store_builder.build())
+}
+
+fn name_to_string(name: &X509NameRef) -> String {
+    name.entries()
+        .map(|entry| {
+            let key = entry.object().nid().short_name().unwrap_or("?");
+            let val = String::from_utf8_lossy(entry.data().as_slice());
+            format!("{}={}", key, val)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn to_hex(bin: &[u8]) -> String {
+    bin.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+///tag, content and the bytes after the first TLV of der, none if it is not definite length
+fn der_tlv(der: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = der.split_first()?;
+    let (&len_byte, rest) = rest.split_first()?;
+    let (len, rest) = if len_byte < 0x80 {
+        (len_byte as usize, rest)
+    } else {
+        let len_num = (len_byte & 0x7f) as usize;
+        if len_num == 0 || len_num > 4 || rest.len() < len_num {
+            return None;
+        }
+        let len = rest[..len_num]
+            .iter()
+            .fold(0, |len, b| len << 8 | *b as usize);
+        (len, &rest[len_num..])
+    };
+    if rest.len() < len {
+        return None;
+    }
+    Some((tag, &rest[..len], &rest[len..]))
+}
+
+///content of der if its tag is tag
+fn der_content(der: &[u8], tag: u8) -> Option<&[u8]> {
+    der_tlv(der).and_then(|(der_tag, content, _)| (der_tag == tag).then_some(content))
+}
+
+///tags and contents of the TLVs in a constructed content
+fn der_children(mut content: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut children = vec![];
+    while let Some((tag, child, rest)) = der_tlv(content) {
+        children.push((tag, child));
+        content = rest;
+    }
+    children
+}
+
+///values SET of the oid attribute in signedAttrs of the first SignerInfo,
+///ContentInfo { contentType, [0] SignedData { .., [0] certificates, [1] crls, signerInfos } }
+fn signed_attr<'a>(pkcs7_der: &'a [u8], oid: &[u8]) -> Option<&'a [u8]> {
+    let signed_data = match der_children(der_content(pkcs7_der, 0x30)?).as_slice() {
+        [(0x06, _), (0xa0, explicit), ..] => der_content(explicit, 0x30)?,
+        _ => return None,
+    };
+    let signer_infos = match der_children(signed_data).last() {
+        Some((0x31, signer_infos)) => *signer_infos,
+        _ => return None,
+    };
+    //signedAttrs is [0] IMPLICIT SET, a subjectKeyIdentifier sid is primitive [0]
+    let signed_attrs = der_children(der_content(signer_infos, 0x30)?)
+        .into_iter()
+        .find(|(tag, _)| *tag == 0xa0)?
+        .1;
+    der_children(signed_attrs)
+        .into_iter()
+        .find_map(|(tag, attr)| match (tag, der_children(attr).as_slice()) {
+            (0x30, [(0x06, attr_oid), (0x31, values)]) if *attr_oid == oid => Some(*values),
+            _ => None,
+        })
+}
+
+///UTCTime or GeneralizedTime of the signer's signingTime attribute, empty if it has none
+fn signing_time(pkcs7_der: &[u8]) -> String {
+    let time = signed_attr(pkcs7_der, &SIGNING_TIME_OID).and_then(|values| {
+        der_tlv(values).and_then(|(tag, time, _)| matches!(tag, 0x17 | 0x18).then_some(time))
+    });
+    time.and_then(|time| std::str::from_utf8(time).ok())
+        .and_then(|time| Asn1Time::from_str(time).ok())
+        .map(|time| time.to_string())
+        .unwrap_or_default()
+}
+
+///ed25519 key or ecdsa key on p-256
+fn check_key_scheme<T: HasPublic>(pkey: &PKeyRef<T>, scheme: Uchar) -> Result<(), CrateSpecError> {
+    let matched = match scheme {
+        SIG_SCHEME_ED25519 => pkey.id() == Id::ED25519,
+        SIG_SCHEME_P256 => pkey
+            .ec_key()
+            .map(|ec_key| ec_key.group().curve_name() == Some(Nid::X9_62_PRIME256V1))
+            .unwrap_or(false),
+        _ => false,
+    };
+    if !matched {
+        return Err(CrateSpecError::Signature(format!(
+            "key does not fit sig scheme {}",
+            scheme
+        )));
+    }
+    Ok(())
+}
+
+fn key_id<T: HasPublic>(pkey: &PKeyRef<T>) -> Result<Vec<u8>, CrateSpecError> {
+    let sig_err = |e: openssl::error::ErrorStack| CrateSpecError::Signature(e.to_string());
+    let der = pkey.public_key_to_der().map_err(sig_err)?;
+    Ok(hash(MessageDigest::sha256(), &der)
+        .map_err(sig_err)?
+        .to_vec())
+}
+
+fn chain_certs(chain_bin: &[u8]) -> Result<Vec<X509>, CrateSpecError> {
+    let chain = X509::stack_from_pem(chain_bin)
+        .map_err(|e| CrateSpecError::Signature(format!("certificate chain not right - {}", e)))?;
+    if chain.is_empty() {
+        return Err(CrateSpecError::Signature(
+            "certificate chain not right - no certificate found".to_string(),
+        ));
+    }
+    Ok(chain)
+}
+
 impl PKCS {
     pub fn new() -> Self {
         Self {
             cert_bin: vec![],
             pkey_bin: vec![],
             root_ca_bins: vec![],
+            chain_bins: vec![],
+            scheme: SIG_SCHEME_CMS,
         }
     }
-    pub fn root_ca_bins(ca_paths: Vec<String>) -> Vec<Vec<u8>> {
+
+    pub fn scheme(&self) -> Uchar {
+        self.scheme
+    }
+
+    ///scheme written at c_version, versions before 9 have no sig scheme and hold S/MIME
+    pub fn scheme_by_version(&self, c_version: Uchar) -> Uchar {
+        match self.scheme {
+            SIG_SCHEME_CMS if c_version < 9 => SIG_SCHEME_PKCS7,
+            scheme => scheme,
+        }
+    }
+    pub fn root_ca_bins(ca_paths: Vec<String>) -> Result<Vec<Vec<u8>>, CrateSpecError> {
         let mut root_ca_bins = vec![];
         for ca_path in ca_paths {
-            root_ca_bins.push(fs::read(Path::new(ca_path.as_str())).unwrap());
+            root_ca_bins.push(read_file(ca_path.as_str())?);
         }
-        root_ca_bins
+        Ok(root_ca_bins)
     }
 
     pub fn load_from_file_writer(
@@ -44,69 +263,302 @@ impl PKCS {
         cert_path: String,
         pkey_path: String,
         ca_paths: Vec<String>,
-    ) {
+    ) -> Result<(), CrateSpecError> {
         //just for demo
-        self.cert_bin = fs::read(Path::new(cert_path.as_str())).unwrap();
-        self.pkey_bin = fs::read(Path::new(pkey_path.as_str())).unwrap();
-        for ca_path in ca_paths {
-            self.root_ca_bins
-                .push(fs::read(Path::new(ca_path.as_str())).unwrap());
+        self.cert_bin = read_file(cert_path.as_str())?;
+        self.pkey_bin = read_file(pkey_path.as_str())?;
+        self.root_ca_bins.extend(Self::root_ca_bins(ca_paths)?);
+        Ok(())
+    }
+
+    ///certificate scheme to sign with, CMS if not set
+    pub fn set_cert_scheme(&mut self, scheme: SIGSCHEME) -> Result<(), CrateSpecError> {
+        if !matches!(scheme, SIGSCHEME::PKCS7 | SIGSCHEME::CMS) {
+            return Err(CrateSpecError::Signature(format!(
+                "sig scheme {} needs a raw key",
+                scheme.id()
+            )));
         }
+        self.scheme = scheme.id();
+        Ok(())
     }
 
-    pub fn load_from_file_reader(&mut self, ca_paths: Vec<String>) {
+    ///intermediate certificates from signer certificate up to root-ca, a file may hold several
+    pub fn load_chain_from_file(&mut self, chain_paths: Vec<String>) -> Result<(), CrateSpecError> {
+        for chain_path in chain_paths {
+            let chain_bin = read_file(chain_path.as_str())?;
+            chain_certs(&chain_bin)?;
+            self.chain_bins.push(chain_bin);
+        }
+        Ok(())
+    }
+
+    ///private key of a raw signature scheme, no certificate is needed
+    pub fn load_raw_key_from_file(
+        &mut self,
+        pkey_path: String,
+        scheme: SIGSCHEME,
+    ) -> Result<(), CrateSpecError> {
+        let pkey_bin = read_file(pkey_path.as_str())?;
+        let pkey = PKey::private_key_from_pem(pkey_bin.as_slice())
+            .map_err(|e| CrateSpecError::Signature(format!("key not right - {}", e)))?;
+        check_key_scheme(&pkey, scheme.id())?;
+        self.pkey_bin = pkey_bin;
+        self.scheme = scheme.id();
+        Ok(())
+    }
+
+    ///public keys raw signatures are verified against, one pem file per key
+    pub fn keyring_bins(key_paths: Vec<String>) -> Result<Vec<Vec<u8>>, CrateSpecError> {
+        let mut keyring_bins = vec![];
+        for key_path in key_paths {
+            let key_bin = read_file(key_path.as_str())?;
+            PKey::public_key_from_pem(key_bin.as_slice()).map_err(|e| {
+                CrateSpecError::Signature(format!("public key {} not right - {}", key_path, e))
+            })?;
+            keyring_bins.push(key_bin);
+        }
+        Ok(keyring_bins)
+    }
+
+    pub fn load_from_file_reader(&mut self, ca_paths: Vec<String>) -> Result<(), CrateSpecError> {
         //just for demo
-        for ca_path in ca_paths {
-            self.root_ca_bins
-                .push(fs::read(Path::new(ca_path.as_str())).unwrap());
+        self.root_ca_bins.extend(Self::root_ca_bins(ca_paths)?);
+        Ok(())
+    }
+
+    pub fn encode_pkcs_bin(&self, message: &[u8]) -> Result<Vec<u8>, CrateSpecError> {
+        let sig_err = |e: openssl::error::ErrorStack| CrateSpecError::Signature(e.to_string());
+        let cert = X509::from_pem(self.cert_bin.as_slice()).map_err(sig_err)?;
+        let mut certs = Stack::new().map_err(sig_err)?;
+        for chain_bin in self.chain_bins.iter() {
+            for chain_cert in chain_certs(chain_bin)? {
+                certs.push(chain_cert).map_err(sig_err)?;
+            }
         }
+        //digest is raw bytes, BINARY stops smime from translating its line endings
+        let flags = Pkcs7Flags::STREAM | Pkcs7Flags::BINARY;
+        let pkey = PKey::private_key_from_pem(self.pkey_bin.as_slice()).map_err(sig_err)?;
+
+        let _store = root_ca_store(&self.root_ca_bins)?;
+
+        let pkcs7 = Pkcs7::sign(&cert, &pkey, &certs, message, flags).map_err(sig_err)?;
+
+        pkcs7.to_smime(message, flags).map_err(sig_err)
     }
 
-    pub fn encode_pkcs_bin(&self, message: &[u8]) -> Vec<u8> {
-        //FIXME current we don't support middle certs
-        let cert = X509::from_pem(self.cert_bin.as_slice()).unwrap();
-        let certs = Stack::new().unwrap();
-        let flags = Pkcs7Flags::STREAM;
-        let pkey = PKey::private_key_from_pem(self.pkey_bin.as_slice()).unwrap();
-        let mut store_builder = X509StoreBuilder::new().expect("should succeed");
+    ///sign digest by scheme, which is given by scheme_by_version
+    pub fn sign_digest(&self, digest: &[u8], scheme: Uchar) -> Result<Vec<u8>, CrateSpecError> {
+        match scheme {
+            SIG_SCHEME_PKCS7 => self.encode_pkcs_bin(digest),
+            SIG_SCHEME_CMS => self.encode_cms_bin(digest),
+            _ => self.encode_raw_bin(digest),
+        }
+    }
 
-        for root_ca_bin in self.root_ca_bins.iter() {
-            let root_ca = X509::from_pem(root_ca_bin.as_slice()).unwrap();
-            store_builder.add_cert(root_ca).expect("should succeed");
+    ///DER cms SignedData holding message as its content
+    pub fn encode_cms_bin(&self, message: &[u8]) -> Result<Vec<u8>, CrateSpecError> {
+        let sig_err = |e: openssl::error::ErrorStack| CrateSpecError::Signature(e.to_string());
+        let cert = X509::from_pem(self.cert_bin.as_slice()).map_err(sig_err)?;
+        let mut certs = Stack::new().map_err(sig_err)?;
+        for chain_bin in self.chain_bins.iter() {
+            for chain_cert in chain_certs(chain_bin)? {
+                certs.push(chain_cert).map_err(sig_err)?;
+            }
         }
+        let flags = CMSOptions::BINARY | CMSOptions::NOSMIMECAP;
+        let pkey = PKey::private_key_from_pem(self.pkey_bin.as_slice()).map_err(sig_err)?;
 
-        let _store = store_builder.build();
+        let cms =
+            CmsContentInfo::sign(Some(&cert), Some(&pkey), Some(&certs), Some(message), flags)
+                .map_err(sig_err)?;
 
-        let pkcs7 = Pkcs7::sign(&cert, &pkey, &certs, message, flags).expect("should succeed");
+        cms.to_der().map_err(sig_err)
+    }
 
-        pkcs7.to_smime(message, flags).expect("should succeed")
+    ///key id followed by the signature over digest
+    pub fn encode_raw_bin(&self, digest: &[u8]) -> Result<Vec<u8>, CrateSpecError> {
+        let sig_err = |e: openssl::error::ErrorStack| CrateSpecError::Signature(e.to_string());
+        let pkey = PKey::private_key_from_pem(self.pkey_bin.as_slice()).map_err(sig_err)?;
+        check_key_scheme(&pkey, self.scheme)?;
+        let mut signed = key_id(&pkey)?;
+        if self.scheme == SIG_SCHEME_ED25519 {
+            let mut signer = Signer::new_without_digest(&pkey).map_err(sig_err)?;
+            signed.extend(signer.sign_oneshot_to_vec(digest).map_err(sig_err)?);
+        } else {
+            //digest is sha256 already, so this is ecdsa-with-sha256 over the signed bytes
+            let ec_key = pkey.ec_key().map_err(sig_err)?;
+            let sig = EcdsaSig::sign(digest, &ec_key).map_err(sig_err)?;
+            signed.extend(sig.r().to_vec_padded(32).map_err(sig_err)?);
+            signed.extend(sig.s().to_vec_padded(32).map_err(sig_err)?);
+        }
+        Ok(signed)
     }
 
-    pub fn decode_pkcs_bin(signed_bin: &[u8], root_ca_bins: &[Vec<u8>]) -> Vec<u8> {
+    ///whether signed_bin signs digest by a key in keyring,
+    ///a key id not found in keyring is not trusted
+    pub fn decode_raw_bin(
+        scheme: Uchar,
+        signed_bin: &[u8],
+        digest: &[u8],
+        keyring_bins: &[Vec<u8>],
+    ) -> Result<bool, CrateSpecError> {
+        let sig_err = |e: openssl::error::ErrorStack| CrateSpecError::Signature(e.to_string());
+        if signed_bin.len() != RAW_KEY_ID_LEN + RAW_SIG_LEN {
+            return Err(CrateSpecError::Signature(format!(
+                "sig not right - {} bytes",
+                signed_bin.len()
+            )));
+        }
+        let (id, sig) = signed_bin.split_at(RAW_KEY_ID_LEN);
+        for key_bin in keyring_bins.iter() {
+            let pkey = PKey::public_key_from_pem(key_bin.as_slice()).map_err(sig_err)?;
+            if key_id(&pkey)? != id || check_key_scheme(&pkey, scheme).is_err() {
+                continue;
+            }
+            if scheme == SIG_SCHEME_ED25519 {
+                let mut verifier = Verifier::new_without_digest(&pkey).map_err(sig_err)?;
+                return Ok(verifier.verify_oneshot(sig, digest).unwrap_or(false));
+            }
+            let (r, s) = sig.split_at(RAW_SIG_LEN / 2);
+            let sig = EcdsaSig::from_private_components(
+                BigNum::from_slice(r).map_err(sig_err)?,
+                BigNum::from_slice(s).map_err(sig_err)?,
+            )
+            .map_err(sig_err)?;
+            let ec_key = pkey.ec_key().map_err(sig_err)?;
+            return Ok(sig.verify(digest, &ec_key).unwrap_or(false));
+        }
+        Err(CrateSpecError::Trust(format!(
+            "key {} not in keyring",
+            to_hex(id)
+        )))
+    }
+
+    ///key id of a raw signature, signed_bin should be verified by decode_raw_bin
+    pub fn raw_signer_info(signed_bin: &[u8]) -> Result<SignerInfo, CrateSpecError> {
+        let id = signed_bin
+            .get(..RAW_KEY_ID_LEN)
+            .ok_or_else(|| CrateSpecError::Signature("sig not right".to_string()))?;
+        Ok(SignerInfo {
+            fingerprint: to_hex(id),
+            ..Default::default()
+        })
+    }
+
+    pub fn decode_pkcs_bin(
+        signed_bin: &[u8],
+        root_ca_bins: &[Vec<u8>],
+    ) -> Result<Vec<u8>, CrateSpecError> {
         //FIXME maybe all pkcs section should share same root cas
-        let certs = Stack::new().unwrap();
-        let flags = Pkcs7Flags::STREAM;
-        let mut store_builder = X509StoreBuilder::new().expect("should succeed");
+        let sig_err = |e: openssl::error::ErrorStack| CrateSpecError::Signature(e.to_string());
+        let certs = Stack::new().map_err(sig_err)?;
+        let flags = Pkcs7Flags::STREAM | Pkcs7Flags::BINARY;
+
+        let store = root_ca_store(root_ca_bins)?;
 
-        for root_ca_bin in root_ca_bins.iter() {
-            let root_ca = X509::from_pem(root_ca_bin.as_slice()).unwrap();
-            store_builder.add_cert(root_ca).expect("should succeed");
+        let (pkcs7_decoded, _content) = Pkcs7::from_smime(signed_bin)
+            .map_err(|e| CrateSpecError::Signature(format!("sig not right - {}", e)))?;
+
+        //intermediate certificates embedded by the signer are used to build the path to root-ca
+        let mut output = Vec::new();
+        if let Err(e) = pkcs7_decoded.verify(&certs, &store, None, Some(&mut output), flags) {
+            //the signature itself is sound, so only the certificate chain can be wrong
+            let mut no_verify_output = Vec::new();
+            return match pkcs7_decoded.verify(
+                &certs,
+                &store,
+                None,
+                Some(&mut no_verify_output),
+                flags | Pkcs7Flags::NOVERIFY,
+            ) {
+                Ok(_) => Err(CrateSpecError::Trust(e.to_string())),
+                Err(_) => Err(CrateSpecError::Signature(format!("sig not right - {}", e))),
+            };
         }
+        Ok(output)
+    }
 
-        let store = store_builder.build();
+    ///signer certificate embedded in the signature, it is not verified
+    fn signer_cert(pkcs7: &Pkcs7) -> Result<X509, CrateSpecError> {
+        let sig_err = |e: openssl::error::ErrorStack| CrateSpecError::Signature(e.to_string());
+        let certs = Stack::new().map_err(sig_err)?;
+        let signers = pkcs7
+            .signers(&certs, Pkcs7Flags::empty())
+            .map_err(sig_err)?;
+        let cert = signers
+            .iter()
+            .next()
+            .ok_or_else(|| CrateSpecError::Signature("signer not found".to_string()))?;
+        Ok(cert.to_owned())
+    }
+
+    ///content of a DER cms SignedData whose signer chains to root-ca
+    pub fn decode_cms_bin(
+        signed_bin: &[u8],
+        root_ca_bins: &[Vec<u8>],
+    ) -> Result<Vec<u8>, CrateSpecError> {
+        let flags = CMSOptions::BINARY;
+        let store = root_ca_store(root_ca_bins)?;
 
-        let (pkcs7_decoded, _content) = Pkcs7::from_smime(signed_bin).expect("should succeed");
+        let mut cms = CmsContentInfo::from_der(signed_bin)
+            .map_err(|e| CrateSpecError::Signature(format!("sig not right - {}", e)))?;
 
+        //intermediate certificates embedded by the signer are used to build the path to root-ca
         let mut output = Vec::new();
-        pkcs7_decoded
-            .verify(&certs, &store, None, Some(&mut output), flags)
-            .expect("should succeed");
-        output
+        if let Err(e) = cms.verify(None, Some(&store), None, Some(&mut output), flags) {
+            //the signature itself is sound, so only the certificate chain can be wrong
+            return match cms.verify(None, Some(&store), None, None, flags | CMSOptions::NOVERIFY) {
+                Ok(_) => Err(CrateSpecError::Trust(e.to_string())),
+                Err(_) => Err(CrateSpecError::Signature(format!("sig not right - {}", e))),
+            };
+        }
+        Ok(output)
     }
 
-    pub fn gen_digest_256(&self, bin: &[u8]) -> Vec<u8> {
-        let res = hash(MessageDigest::sha256(), bin).unwrap();
-        res.to_vec()
+    ///S/MIME, or DER cms SignedData which pkcs7 reads as well
+    fn read_pkcs7(signed_bin: &[u8], scheme: Uchar) -> Result<Pkcs7, CrateSpecError> {
+        let pkcs7 = match scheme {
+            SIG_SCHEME_CMS => Pkcs7::from_der(signed_bin),
+            _ => Pkcs7::from_smime(signed_bin).map(|(pkcs7, _content)| pkcs7),
+        };
+        pkcs7.map_err(|e| CrateSpecError::Signature(format!("sig not right - {}", e)))
+    }
+
+    ///signer certificate and signing time, signed_bin should be verified by decode_pkcs_bin
+    ///or decode_cms_bin
+    pub fn signer_info(signed_bin: &[u8], scheme: Uchar) -> Result<SignerInfo, CrateSpecError> {
+        let sig_err = |e: openssl::error::ErrorStack| CrateSpecError::Signature(e.to_string());
+        let pkcs7 = Self::read_pkcs7(signed_bin, scheme)?;
+        let cert = Self::signer_cert(&pkcs7)?;
+        Ok(SignerInfo {
+            subject: name_to_string(cert.subject_name()),
+            issuer: name_to_string(cert.issuer_name()),
+            serial: cert
+                .serial_number()
+                .to_bn()
+                .and_then(|serial| serial.to_hex_str().map(|serial| serial.to_string()))
+                .map_err(sig_err)?,
+            fingerprint: to_hex(&cert.digest(MessageDigest::sha256()).map_err(sig_err)?),
+            not_before: cert.not_before().to_string(),
+            not_after: cert.not_after().to_string(),
+            signing_time: signing_time(&pkcs7.to_der().map_err(sig_err)?),
+        })
+    }
+
+    ///whether the signer certificate is out of its validity period now
+    pub fn is_signer_expired(signed_bin: &[u8], scheme: Uchar) -> Result<bool, CrateSpecError> {
+        let sig_err = |e: openssl::error::ErrorStack| CrateSpecError::Signature(e.to_string());
+        let pkcs7 = Self::read_pkcs7(signed_bin, scheme)?;
+        let cert = Self::signer_cert(&pkcs7)?;
+        let now = Asn1Time::days_from_now(0).map_err(sig_err)?;
+        Ok(cert.not_after() < now || cert.not_before() > now)
+    }
+
+    pub fn gen_digest_256(&self, bin: &[u8]) -> Result<Vec<u8>, CrateSpecError> {
+        let res = hash(MessageDigest::sha256(), bin)
+            .map_err(|e| CrateSpecError::Signature(e.to_string()))?;
+        Ok(res.to_vec())
     }
 }
 
@@ -115,6 +567,142 @@ impl Default for PKCS {
         Self::new()
     }
 }
+
+#[test]
+fn test_pkcs_chain() {
+    let root_ca_bins = PKCS::root_ca_bins(["test/root-ca.pem".to_string()].to_vec()).unwrap();
+    let mut pkcs = PKCS::new();
+    pkcs.load_from_file_writer(
+        "test/chain-cert.pem".to_string(),
+        "test/chain-key.pem".to_string(),
+        ["test/root-ca.pem".to_string()].to_vec(),
+    )
+    .unwrap();
+    let digest = pkcs.gen_digest_256(b"Hello rust!").unwrap();
+
+    //signer certificate is issued by an intermediate root-ca does not know
+    let signed = pkcs.encode_pkcs_bin(digest.as_slice()).unwrap();
+    assert!(matches!(
+        PKCS::decode_pkcs_bin(signed.as_slice(), &root_ca_bins),
+        Err(CrateSpecError::Trust(_))
+    ));
+
+    pkcs.load_chain_from_file(["test/intermediate-ca.pem".to_string()].to_vec())
+        .unwrap();
+    let signed = pkcs.encode_pkcs_bin(digest.as_slice()).unwrap();
+    assert_eq!(
+        digest,
+        PKCS::decode_pkcs_bin(signed.as_slice(), &root_ca_bins).unwrap()
+    );
+    let signer = PKCS::signer_info(signed.as_slice(), SIG_SCHEME_PKCS7).unwrap();
+    assert!(signer.subject.ends_with("CN=chain.foobar.com"));
+    assert!(signer.issuer.ends_with("CN=intermediate"));
+    assert_eq!(64, signer.fingerprint.len());
+    assert!(signer.signing_time.ends_with("GMT"));
+
+    assert!(pkcs
+        .load_chain_from_file(["test/test.toml".to_string()].to_vec())
+        .is_err());
+}
+
+#[test]
+fn test_cms() {
+    let root_ca_bins = PKCS::root_ca_bins(["test/root-ca.pem".to_string()].to_vec()).unwrap();
+    let mut pkcs = PKCS::new();
+    pkcs.load_from_file_writer(
+        "test/chain-cert.pem".to_string(),
+        "test/chain-key.pem".to_string(),
+        ["test/root-ca.pem".to_string()].to_vec(),
+    )
+    .unwrap();
+    pkcs.load_chain_from_file(["test/intermediate-ca.pem".to_string()].to_vec())
+        .unwrap();
+    let digest = pkcs.gen_digest_256(b"Hello rust!").unwrap();
+    assert_eq!(SIG_SCHEME_CMS, SIGSCHEME::CMS.id());
+    assert_eq!(SIG_SCHEME_PKCS7, SIGSCHEME::PKCS7.id());
+    assert_eq!(SIG_SCHEME_CMS, pkcs.scheme_by_version(9));
+    assert_eq!(SIG_SCHEME_PKCS7, pkcs.scheme_by_version(8));
+
+    //S/MIME is kept at version 9 when chosen
+    let mut smime_pkcs = PKCS::new();
+    smime_pkcs.set_cert_scheme(SIGSCHEME::PKCS7).unwrap();
+    assert_eq!(SIG_SCHEME_PKCS7, smime_pkcs.scheme_by_version(9));
+    assert!(smime_pkcs.set_cert_scheme(SIGSCHEME::ED25519).is_err());
+
+    let cms = pkcs.sign_digest(&digest, SIG_SCHEME_CMS).unwrap();
+    let smime = pkcs.sign_digest(&digest, SIG_SCHEME_PKCS7).unwrap();
+    assert!(cms.len() < smime.len());
+    assert_eq!(digest, PKCS::decode_cms_bin(&cms, &root_ca_bins).unwrap());
+    assert_eq!(
+        digest,
+        PKCS::decode_pkcs_bin(&smime, &root_ca_bins).unwrap()
+    );
+    assert_eq!(
+        PKCS::signer_info(&smime, SIG_SCHEME_PKCS7).unwrap().subject,
+        PKCS::signer_info(&cms, SIG_SCHEME_CMS).unwrap().subject
+    );
+    assert!(PKCS::signer_info(&cms, SIG_SCHEME_CMS)
+        .unwrap()
+        .signing_time
+        .ends_with("GMT"));
+
+    //one format is not read as the other
+    assert!(matches!(
+        PKCS::decode_cms_bin(&smime, &root_ca_bins),
+        Err(CrateSpecError::Signature(_))
+    ));
+    assert!(matches!(
+        PKCS::decode_cms_bin(&cms, &root_ca_bins[..0]),
+        Err(CrateSpecError::Trust(_))
+    ));
+}
+
+#[test]
+fn test_raw_sig() {
+    let keyring = PKCS::keyring_bins(
+        [
+            "test/ed25519-pub.pem".to_string(),
+            "test/p256-pub.pem".to_string(),
+        ]
+        .to_vec(),
+    )
+    .unwrap();
+    let digest = PKCS::new().gen_digest_256(b"Hello rust!").unwrap();
+    let other_digest = PKCS::new().gen_digest_256(b"Hello crate!").unwrap();
+    for (key_path, scheme) in [
+        ("test/ed25519-key.pem", SIGSCHEME::ED25519),
+        ("test/p256-key.pem", SIGSCHEME::P256),
+    ] {
+        let scheme_id = scheme.id();
+        let mut pkcs = PKCS::new();
+        pkcs.load_raw_key_from_file(key_path.to_string(), scheme)
+            .unwrap();
+        let signed = pkcs.sign_digest(digest.as_slice(), scheme_id).unwrap();
+        assert_eq!(RAW_KEY_ID_LEN + RAW_SIG_LEN, signed.len());
+        assert!(PKCS::decode_raw_bin(scheme_id, &signed, &digest, &keyring).unwrap());
+        assert!(!PKCS::decode_raw_bin(scheme_id, &signed, &other_digest, &keyring).unwrap());
+        assert!(matches!(
+            PKCS::decode_raw_bin(scheme_id, &signed, &digest, &keyring[..0]),
+            Err(CrateSpecError::Trust(_))
+        ));
+        assert!(matches!(
+            PKCS::decode_raw_bin(scheme_id, &signed[1..], &digest, &keyring),
+            Err(CrateSpecError::Signature(_))
+        ));
+        assert_eq!(
+            64,
+            PKCS::raw_signer_info(&signed).unwrap().fingerprint.len()
+        );
+    }
+
+    //key and scheme have to fit
+    assert!(PKCS::new()
+        .load_raw_key_from_file("test/key.pem".to_string(), SIGSCHEME::ED25519)
+        .is_err());
+    assert!(PKCS::new()
+        .load_raw_key_from_file("test/ed25519-key.pem".to_string(), SIGSCHEME::P256)
+        .is_err());
+}
 // #[test]
 // fn test_pkcs(){
 //     let mut pkcs = PKCS::new();
@@ -185,3 +773,40 @@ impl Default for PKCS {
 //     //assert_eq!(&*res, spec);
 //     Ok(())
 // }
+
+#[test]
+fn test_signing_time() {
+    fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut der = vec![tag, content.len() as u8];
+        der.extend(content);
+        der
+    }
+    fn attr(time: &str) -> Vec<u8> {
+        let mut attr = tlv(0x06, &SIGNING_TIME_OID);
+        attr.extend(tlv(0x31, &tlv(0x17, time.as_bytes())));
+        tlv(0x30, &attr)
+    }
+    fn pkcs7_der(certificates: &[u8], signed_attrs: &[u8]) -> Vec<u8> {
+        let mut signer_info = tlv(0x02, &[1]);
+        signer_info.extend(tlv(0x80, &[1, 2, 3]));
+        signer_info.extend(tlv(0x30, &[]));
+        if !signed_attrs.is_empty() {
+            signer_info.extend(tlv(0xa0, signed_attrs));
+        }
+        let mut signed_data = tlv(0x02, &[1]);
+        signed_data.extend(tlv(0x31, &[]));
+        signed_data.extend(tlv(0x30, &[]));
+        signed_data.extend(tlv(0xa0, certificates));
+        signed_data.extend(tlv(0x31, &tlv(0x30, &signer_info)));
+        let mut content_info = tlv(0x06, &[0x2a]);
+        content_info.extend(tlv(0xa0, &tlv(0x30, &signed_data)));
+        tlv(0x30, &content_info)
+    }
+
+    let der = pkcs7_der(&attr("230101000000Z"), &attr("240101000000Z"));
+    assert_eq!("Jan  1 00:00:00 2024 GMT", signing_time(&der));
+    //signingTime bytes inside the certificates are not the signer's
+    let der = pkcs7_der(&attr("230101000000Z"), &[]);
+    assert_eq!("", signing_time(&der));
+    assert_eq!("", signing_time(&der[..der.len() - 1]));
+}
@@ -0,0 +1,172 @@
+//!per-signature verification report and the policy deciding whether a package is accepted
+use crate::utils::error::CrateSpecError;
+
+///result of verifying one signature section
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SigStatus {
+    Valid,
+    ///signature is sound but signs another digest
+    DigestMismatch,
+    ///signer certificate does not chain to root-ca
+    Untrusted,
+    ///signer certificate is out of its validity period
+    Expired,
+    ///signature can not be parsed or its type is unknown
+    Malformed,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SigReport {
+    ///position of the signature among signature sections
+    pub index: usize,
+    pub typ: u32,
+    pub status: SigStatus,
+    ///empty if the signature is valid
+    pub reason: String,
+}
+
+impl SigReport {
+    pub fn is_valid(&self) -> bool {
+        self.status == SigStatus::Valid
+    }
+
+    ///error a package is rejected with because of this signature
+    pub fn to_error(&self) -> CrateSpecError {
+        match self.status {
+            SigStatus::Untrusted | SigStatus::Expired => CrateSpecError::Trust(self.reason.clone()),
+            _ => CrateSpecError::Signature(self.reason.clone()),
+        }
+    }
+}
+
+///one entry for every signature section, in file order
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct VerificationReport {
+    pub sigs: Vec<SigReport>,
+}
+
+impl VerificationReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn valid_num(&self) -> usize {
+        self.sigs.iter().filter(|sig| sig.is_valid()).count()
+    }
+
+    pub fn first_invalid(&self) -> Option<&SigReport> {
+        self.sigs.iter().find(|sig| !sig.is_valid())
+    }
+}
+
+///which signatures have to be valid for a package to be accepted
+#[derive(Debug, PartialEq, Clone)]
+pub struct VerificationPolicy {
+    ///every signature has to be valid
+    pub require_all: bool,
+    ///least number of valid signatures
+    pub min_valid: usize,
+    ///sig types which need at least one valid signature each
+    pub required_types: Vec<u32>,
+}
+
+impl VerificationPolicy {
+    ///every signature has to be valid
+    pub fn new() -> Self {
+        Self {
+            require_all: true,
+            min_valid: 0,
+            required_types: vec![],
+        }
+    }
+
+    ///at least min_valid signatures have to be valid, others are only reported
+    pub fn at_least(min_valid: usize) -> Self {
+        Self {
+            require_all: false,
+            min_valid,
+            required_types: vec![],
+        }
+    }
+
+    pub fn require_type(mut self, typ: u32) -> Self {
+        self.required_types.push(typ);
+        self
+    }
+
+    pub fn check(&self, report: &VerificationReport) -> Result<(), CrateSpecError> {
+        if self.require_all {
+            if let Some(sig) = report.first_invalid() {
+                return Err(sig.to_error());
+            }
+        }
+        if report.valid_num() < self.min_valid {
+            return Err(CrateSpecError::Signature(format!(
+                "{} of {} sigs valid, {} required",
+                report.valid_num(),
+                report.sigs.len(),
+                self.min_valid
+            )));
+        }
+        for typ in self.required_types.iter() {
+            if !report
+                .sigs
+                .iter()
+                .any(|sig| sig.typ == *typ && sig.is_valid())
+            {
+                return Err(CrateSpecError::Signature(format!(
+                    "no valid sig of type {}",
+                    typ
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for VerificationPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn test_verification_policy() {
+    let sig = |index: usize, typ: u32, status: SigStatus| SigReport {
+        index,
+        typ,
+        status,
+        reason: if status == SigStatus::Valid {
+            "".to_string()
+        } else {
+            "revoked auditor".to_string()
+        },
+    };
+    let report = VerificationReport {
+        sigs: vec![
+            sig(0, 1, SigStatus::Valid),
+            sig(1, 2, SigStatus::Valid),
+            sig(2, 1, SigStatus::Untrusted),
+        ],
+    };
+    assert_eq!(2, report.valid_num());
+    assert_eq!(Some(&report.sigs[2]), report.first_invalid());
+
+    assert!(matches!(
+        VerificationPolicy::new().check(&report),
+        Err(CrateSpecError::Trust(_))
+    ));
+    assert!(VerificationPolicy::at_least(2).check(&report).is_ok());
+    assert!(VerificationPolicy::at_least(3).check(&report).is_err());
+    assert!(VerificationPolicy::at_least(1)
+        .require_type(2)
+        .check(&report)
+        .is_ok());
+    assert!(VerificationPolicy::at_least(1)
+        .require_type(0)
+        .check(&report)
+        .is_err());
+    assert!(VerificationPolicy::new()
+        .check(&VerificationReport::new())
+        .is_ok());
+}